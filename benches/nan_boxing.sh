@@ -0,0 +1,32 @@
+#!/bin/sh
+#
+# Times every benchmark with and without the `nan-boxing` feature.
+#
+# Usage: benches/nan_boxing.sh [runs]
+
+set -e
+
+cd "$(dirname "$0")/.."
+runs="${1:-5}"
+
+cargo build --quiet --release --target-dir target/enum
+cargo build --quiet --release --target-dir target/nan-boxing --features nan-boxing
+
+for program in benches/*.lox; do
+  for variant in enum nan-boxing; do
+    binary="target/$variant/release/codecrafters-interpreter"
+    best=""
+    i=0
+    while [ "$i" -lt "$runs" ]; do
+      start=$(date +%s%N)
+      "$binary" run "$program" > /dev/null
+      end=$(date +%s%N)
+      elapsed=$(( (end - start) / 1000000 ))
+      if [ -z "$best" ] || [ "$elapsed" -lt "$best" ]; then
+        best=$elapsed
+      fi
+      i=$((i + 1))
+    done
+    printf '%-28s %-12s %6d ms\n' "$(basename "$program")" "$variant" "$best"
+  done
+done
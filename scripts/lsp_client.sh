@@ -0,0 +1,32 @@
+#!/bin/sh
+#
+# Drives the language server through a scripted session and prints its
+# replies, one JSON message per line.
+#
+# Each line of the session file is one JSON message to send. `$TEXT` in a
+# line is replaced with the contents of the program, as a JSON string body,
+# so a session can open it with `didOpen`. The server exits when the
+# session ends.
+#
+# Usage: scripts/lsp_client.sh <session.jsonl> [program.lox]
+
+set -e
+
+cd "$(dirname "$0")/.."
+session="$1"
+program="${2:-/dev/null}"
+
+cargo build --quiet
+binary="target/debug/codecrafters-interpreter"
+
+TEXT=$(sed -e 's/\\/\\\\/g' -e 's/"/\\"/g' -e 's/\t/\\t/g' "$program" | awk '{ printf "%s\\n", $0 }')
+export TEXT
+
+awk '{
+  i = index($0, "$TEXT")
+  if (i) $0 = substr($0, 1, i - 1) ENVIRON["TEXT"] substr($0, i + 5)
+  print
+}' "$session" | while IFS= read -r message; do
+  [ -z "$message" ] && continue
+  printf 'Content-Length: %d\r\n\r\n%s' "$(printf '%s' "$message" | wc -c)" "$message"
+done | "$binary" lsp | sed -e 's/Content-Length: [0-9]*\r$//' -e '/^\r*$/d'
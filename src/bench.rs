@@ -0,0 +1,142 @@
+#[cfg(feature = "count-allocations")]
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::process::{self, Command, Stdio};
+#[cfg(feature = "count-allocations")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::run::{interpret, set_silent};
+use crate::utils::flag_value;
+
+const DEFAULT_RUNS: usize = 10;
+
+// Counts heap allocations so `bench` can report them. The overhead is one
+// relaxed atomic add per allocation, paid by every command, so it's only
+// installed with the `count-allocations` feature.
+#[cfg(feature = "count-allocations")]
+struct CountingAllocator;
+
+#[cfg(feature = "count-allocations")]
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(feature = "count-allocations")]
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[cfg(feature = "count-allocations")]
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+// The allocations made so far, if they're being counted.
+#[cfg(feature = "count-allocations")]
+fn allocations() -> Option<usize> {
+    Some(ALLOCATIONS.load(Ordering::Relaxed))
+}
+
+#[cfg(not(feature = "count-allocations"))]
+fn allocations() -> Option<usize> {
+    None
+}
+
+struct Sample {
+    time: Duration,
+    allocations: Option<usize>,
+}
+
+/// Runs a script `--runs` times with `print` output discarded and reports
+/// wall time per run, and allocations per run in builds with the
+/// `count-allocations` feature. With `--compare <interpreter>`, also
+/// times `<interpreter> run <file>` the same number of times, e.g. a build
+/// with another backend or feature set.
+pub fn bench(filename: &str, flags: &[String]) {
+    let runs = match flag_value(flags, "--runs") {
+        Some(runs) => runs.parse::<usize>().unwrap_or_else(|_| {
+            eprintln!("--runs expects a number, got {}", runs);
+            process::exit(64);
+        }),
+        None => DEFAULT_RUNS,
+    }
+    .max(1);
+
+    let mut samples = Vec::new();
+    set_silent(true);
+    for _ in 0..runs {
+        let before = allocations();
+        let start = Instant::now();
+        let result = interpret(filename);
+        let time = start.elapsed();
+        let allocations = allocations()
+            .zip(before)
+            .map(|(after, before)| after - before);
+
+        if let Err(code) = result {
+            process::exit(code);
+        }
+        samples.push(Sample { time, allocations });
+    }
+    set_silent(false);
+
+    println!("{}: {} runs", filename, runs);
+    report("run", samples);
+
+    if let Some(interpreter) = flag_value(flags, "--compare") {
+        let samples = (0..runs)
+            .map(|_| run_external(interpreter, filename))
+            .collect();
+        report(interpreter, samples);
+    }
+}
+
+fn run_external(interpreter: &str, filename: &str) -> Sample {
+    let start = Instant::now();
+    let status = Command::new(interpreter)
+        .args(["run", filename])
+        .stdout(Stdio::null())
+        .status();
+    let time = start.elapsed();
+
+    match status {
+        Ok(status) if status.success() => Sample {
+            time,
+            allocations: None,
+        },
+        Ok(status) => {
+            eprintln!("{} failed with {}", interpreter, status);
+            process::exit(status.code().unwrap_or(1));
+        }
+        Err(err) => {
+            eprintln!("Failed to run {}: {}", interpreter, err);
+            process::exit(1);
+        }
+    }
+}
+
+fn report(name: &str, mut samples: Vec<Sample>) {
+    samples.sort_by_key(|sample| sample.time);
+
+    let min = samples[0].time;
+    let median = samples[samples.len() / 2].time;
+    let max = samples[samples.len() - 1].time;
+    let allocations = match samples[0].allocations {
+        Some(allocations) => format!("{} allocations", allocations),
+        None => "allocations not measured".to_string(),
+    };
+
+    println!(
+        "  {}: min {:.3?}, median {:.3?}, max {:.3?}, {}",
+        name, min, median, max, allocations
+    );
+}
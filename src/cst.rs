@@ -0,0 +1,461 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Display};
+use std::iter::once;
+
+use crate::enums::token::{Lexeme, Span, Token};
+use crate::tokenizer::scan;
+
+/// Source text that isn't part of any token.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TriviaKind {
+    Whitespace,
+    Newline,
+    Comment,
+    DocComment,
+    BlockComment,
+    /// Text the tokenizer rejected, like a stray `#` or an unterminated
+    /// string. It's kept so the file still prints back exactly.
+    Skipped,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+}
+
+/// A token with its exact source text and the trivia around it. Trivia on
+/// the rest of a token's line, up to the newline, trails it; everything else
+/// leads the token after it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntaxToken {
+    pub leading: Vec<Trivia>,
+    pub lexeme: Lexeme,
+    pub text: String,
+    pub trailing: Vec<Trivia>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NodeKind {
+    Root,
+    Statement,
+    /// A `{ ... }` block of statements.
+    Block,
+    /// `( ... )`, around a grouping, arguments, parameters or a condition.
+    Group,
+    /// `[ ... ]`, around a list literal or an index.
+    Brackets,
+    /// `{ ... }` around a map literal.
+    Map,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+/// A node of the concrete syntax tree. The tree only records the shape a
+/// formatter needs: statements, blocks and bracketed groups. Printing the
+/// root gives back the source it was built from, byte for byte.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntaxNode {
+    pub kind: NodeKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+/// Tokenizes `source` without losing anything: every byte ends up in a
+/// token's text or trivia. The last token is always `EOF`, with empty text,
+/// and leads with the trivia at the end of the file.
+pub fn lossless_tokens(source: &str) -> Vec<SyntaxToken> {
+    let (lexemes, _) = scan(source, 1);
+    let eof = Lexeme {
+        token: Token::EOF,
+        line_number: 1 + source.matches('\n').count(),
+        span: Span {
+            start: source.len(),
+            end: source.len(),
+        },
+        doc: None,
+    };
+
+    let mut tokens: Vec<SyntaxToken> = Vec::new();
+    let mut position = 0;
+
+    for lexeme in lexemes.into_iter().chain(once(eof)) {
+        let mut leading = trivia(&source[position..lexeme.span.start]);
+        if let Some(previous) = tokens.last_mut() {
+            let newline = leading
+                .iter()
+                .position(|trivia| trivia.kind == TriviaKind::Newline)
+                .unwrap_or(leading.len());
+            previous.trailing = leading.drain(..newline).collect();
+        }
+
+        position = lexeme.span.end;
+        tokens.push(SyntaxToken {
+            leading,
+            text: source[lexeme.span.start..lexeme.span.end].to_string(),
+            lexeme,
+            trailing: Vec::new(),
+        });
+    }
+
+    tokens
+}
+
+// Splits the text between two tokens into trivia.
+fn trivia(text: &str) -> Vec<Trivia> {
+    let mut trivia: Vec<Trivia> = Vec::new();
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let (kind, len) = if rest.starts_with('\n') {
+            (TriviaKind::Newline, 1)
+        } else if rest.starts_with([' ', '\t', '\r']) {
+            let len = rest
+                .find(|c| !matches!(c, ' ' | '\t' | '\r'))
+                .unwrap_or(rest.len());
+            (TriviaKind::Whitespace, len)
+        } else if rest.starts_with("//") {
+            let len = rest.find('\n').unwrap_or(rest.len());
+            let doc = rest.starts_with("///") && !rest.starts_with("////");
+            let kind = if doc {
+                TriviaKind::DocComment
+            } else {
+                TriviaKind::Comment
+            };
+            (kind, len)
+        } else if rest.starts_with("/*") {
+            (TriviaKind::BlockComment, block_comment_len(rest))
+        } else {
+            let len = rest
+                .char_indices()
+                .skip(1)
+                .find(|(i, c)| {
+                    c.is_whitespace()
+                        || rest[*i..].starts_with("//")
+                        || rest[*i..].starts_with("/*")
+                })
+                .map_or(rest.len(), |(i, _)| i);
+            (TriviaKind::Skipped, len)
+        };
+
+        // Runs of skipped text are kept together as one piece.
+        match trivia.last_mut() {
+            Some(last) if kind == TriviaKind::Skipped && last.kind == kind => {
+                last.text.push_str(&rest[..len]);
+            }
+            _ => trivia.push(Trivia {
+                kind,
+                text: rest[..len].to_string(),
+            }),
+        }
+        rest = &rest[len..];
+    }
+
+    trivia
+}
+
+// Block comments nest, and one left open runs to the end of the text.
+fn block_comment_len(text: &str) -> usize {
+    let mut depth = 0;
+    let mut i = 0;
+
+    while i < text.len() {
+        if text[i..].starts_with("/*") {
+            depth += 1;
+            i += 2;
+        } else if text[i..].starts_with("*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += text[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+
+    text.len()
+}
+
+/// Builds the concrete syntax tree for `source`.
+pub fn parse(source: &str) -> SyntaxNode {
+    let mut tokens: Tokens = lossless_tokens(source).into();
+
+    let mut children = statements(&mut tokens, None);
+    children.extend(tokens.into_iter().map(SyntaxElement::Token));
+    let root = SyntaxNode {
+        kind: NodeKind::Root,
+        children,
+    };
+
+    debug_assert_eq!(root.to_string(), source, "the syntax tree lost source text");
+    root
+}
+
+type Tokens = VecDeque<SyntaxToken>;
+
+fn peek_is(tokens: &Tokens, token: &Token) -> bool {
+    tokens
+        .front()
+        .is_some_and(|next| &next.lexeme.token == token)
+}
+
+// The token a node ends with, or should end with if it's complete.
+fn last_token(element: &SyntaxElement) -> Token {
+    match element {
+        SyntaxElement::Token(token) => token.lexeme.token.clone(),
+        SyntaxElement::Node(node) => match node.kind {
+            NodeKind::Group => Token::RightParen,
+            NodeKind::Brackets => Token::RightBracket,
+            _ => Token::RightBrace,
+        },
+    }
+}
+
+// Statements up to `closing` or the end of the file, whichever comes first.
+fn statements(tokens: &mut Tokens, closing: Option<&Token>) -> Vec<SyntaxElement> {
+    let mut statements = Vec::new();
+
+    while !peek_is(tokens, &Token::EOF) && !closing.is_some_and(|closing| peek_is(tokens, closing))
+    {
+        statements.push(SyntaxElement::Node(statement(tokens)));
+    }
+
+    statements
+}
+
+// A statement runs to its `;`, or for a declaration or control flow
+// statement with a body, to the end of its last block. `else`, `catch` and
+// `finally` carry the statement on.
+fn statement(tokens: &mut Tokens) -> SyntaxNode {
+    let first = tokens.front().map(|token| token.lexeme.token.clone());
+    let anonymous_function = first == Some(Token::Fun)
+        && tokens
+            .get(1)
+            .is_some_and(|next| next.lexeme.token == Token::LeftParen);
+    let has_body = !anonymous_function
+        && matches!(
+            first,
+            Some(Token::Fun | Token::If | Token::While | Token::For | Token::Try)
+        );
+
+    let mut children = Vec::new();
+    let mut previous: Option<Token> = None;
+
+    loop {
+        let element = element(tokens, previous.as_ref());
+        let ends = match &element {
+            SyntaxElement::Token(token) => token.lexeme.token == Token::SemiColon,
+            SyntaxElement::Node(node) => {
+                node.kind == NodeKind::Block && (has_body || previous.is_none())
+            }
+        };
+        previous = Some(last_token(&element));
+        children.push(element);
+
+        let continues = peek_is(tokens, &Token::Else)
+            || peek_is(tokens, &Token::Catch)
+            || peek_is(tokens, &Token::Finally);
+        if (ends && !continues)
+            || peek_is(tokens, &Token::EOF)
+            || peek_is(tokens, &Token::RightBrace)
+        {
+            break;
+        }
+    }
+
+    SyntaxNode {
+        kind: NodeKind::Statement,
+        children,
+    }
+}
+
+// The next token, or a bracketed node if it opens one. A `{` starts a block
+// where a statement could start or a body is expected, and a map anywhere
+// else.
+fn element(tokens: &mut Tokens, previous: Option<&Token>) -> SyntaxElement {
+    let Some(token) = tokens.pop_front() else {
+        unreachable!("the token stream always ends with EOF");
+    };
+
+    let (kind, closing) = match token.lexeme.token {
+        Token::LeftParen => (NodeKind::Group, Token::RightParen),
+        Token::LeftBracket => (NodeKind::Brackets, Token::RightBracket),
+        Token::LeftBrace => {
+            let block = matches!(
+                previous,
+                None | Some(Token::RightParen | Token::Else | Token::Try | Token::Finally)
+            );
+            if block {
+                let mut children = vec![SyntaxElement::Token(token)];
+                children.extend(statements(tokens, Some(&Token::RightBrace)));
+                if peek_is(tokens, &Token::RightBrace) {
+                    children.extend(tokens.pop_front().map(SyntaxElement::Token));
+                }
+                return SyntaxElement::Node(SyntaxNode {
+                    kind: NodeKind::Block,
+                    children,
+                });
+            }
+            (NodeKind::Map, Token::RightBrace)
+        }
+        _ => return SyntaxElement::Token(token),
+    };
+
+    let mut children = vec![SyntaxElement::Token(token)];
+    let mut previous: Option<Token> = None;
+    while !peek_is(tokens, &Token::EOF) {
+        let done = peek_is(tokens, &closing);
+        let element = element(tokens, previous.as_ref());
+        previous = Some(last_token(&element));
+        children.push(element);
+        if done {
+            break;
+        }
+    }
+
+    SyntaxElement::Node(SyntaxNode { kind, children })
+}
+
+impl Display for Trivia {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.text)
+    }
+}
+
+impl Display for SyntaxToken {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for trivia in &self.leading {
+            write!(fmt, "{}", trivia)?;
+        }
+        write!(fmt, "{}", self.text)?;
+        for trivia in &self.trailing {
+            write!(fmt, "{}", trivia)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for SyntaxElement {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyntaxElement::Node(node) => write!(fmt, "{}", node),
+            SyntaxElement::Token(token) => write!(fmt, "{}", token),
+        }
+    }
+}
+
+impl Display for SyntaxNode {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for child in &self.children {
+            write!(fmt, "{}", child)?;
+        }
+        Ok(())
+    }
+}
+
+impl SyntaxNode {
+    /// The node's tokens in source order, through every nested node.
+    pub fn tokens(&self) -> Vec<&SyntaxToken> {
+        let mut tokens = Vec::new();
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => tokens.extend(node.tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+
+    /// From the start of the node's first token to the end of its last,
+    /// leaving out the trivia around them.
+    pub fn span(&self) -> Span {
+        let tokens = self.tokens();
+        match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => Span {
+                start: first.lexeme.span.start,
+                end: last.lexeme.span.end,
+            },
+            _ => Span::default(),
+        }
+    }
+
+    /// An indented outline of the tree, one node, token or piece of trivia
+    /// per line, for the `cst` command.
+    pub fn outline(&self) -> String {
+        let mut outline = String::new();
+        self.write_outline(&mut outline, 0);
+        outline
+    }
+
+    fn write_outline(&self, outline: &mut String, depth: usize) {
+        outline.push_str(&format!("{}{:?}\n", "  ".repeat(depth), self.kind));
+
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.write_outline(outline, depth + 1),
+                SyntaxElement::Token(token) => {
+                    let indent = "  ".repeat(depth + 1);
+                    for trivia in &token.leading {
+                        outline
+                            .push_str(&format!("{}{:?} {:?}\n", indent, trivia.kind, trivia.text));
+                    }
+                    outline.push_str(&format!("{}Token {:?}\n", indent, token.text));
+                    for trivia in &token.trailing {
+                        outline
+                            .push_str(&format!("{}{:?} {:?}\n", indent, trivia.kind, trivia.text));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCES: &[&str] = &[
+        "",
+        "var a = 1; // trailing\n\n/// doc\nfun f(x) { return x; }\n",
+        "print \"héllo ${1 + 2} wörld\";\r\n",
+        "/* outer /* nested */ still */ print 1;",
+        // Malformed: stray characters, unbalanced brackets, an unterminated
+        // string and an unterminated block comment.
+        "var # = 1;\n}}} ((( [\nprint \"open",
+        "fun (a, { ] ) }\n\t\t# @ ~\n",
+        "print 1; /* never closed\n",
+        "{ \"unterminated ${ 1 + }",
+    ];
+
+    #[test]
+    fn printing_the_tree_gives_back_the_source() {
+        for source in SOURCES {
+            assert_eq!(parse(source).to_string(), *source);
+        }
+    }
+
+    #[test]
+    fn token_texts_are_their_spans() {
+        for source in SOURCES {
+            let tokens = lossless_tokens(source);
+            assert_eq!(tokens.last().unwrap().lexeme.token, Token::EOF);
+            for token in tokens {
+                let span = token.lexeme.span;
+                assert_eq!(&source[span.start..span.end], token.text);
+            }
+        }
+    }
+
+    #[test]
+    fn comments_on_a_token_line_trail_it() {
+        let tokens = lossless_tokens("a; // note\n// next\nb;");
+        let semicolon = &tokens[1];
+        assert_eq!(semicolon.trailing.last().unwrap().text, "// note");
+        let b = &tokens[2];
+        assert!(b.leading.iter().any(|trivia| trivia.text == "// next"));
+    }
+}
@@ -1,15 +1,18 @@
 use crate::enums::error::Error;
+#[cfg(feature = "nan-boxing")]
+use crate::enums::nan_box::NanBox;
 use crate::enums::statement::Statement;
 use crate::enums::token::Token;
 use crate::evaluator::Value;
+use crate::gc::{self, Trace, Tracer};
 
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
 use std::rc::Rc;
 
 pub type Env = Rc<RefCell<Environment>>;
 
-#[derive(Debug)]
 pub struct Environment {
     pub symbols: HashMap<String, Symbol>,
     pub enclosing: Option<Env>,
@@ -17,23 +20,105 @@ pub struct Environment {
 
 #[derive(Debug, Clone)]
 pub enum Symbol {
-    Variable(Value),
-    Function(Vec<Token>, Statement),
+    Variable(Slot),
+    /// A `const` binding, which can be read like a variable but never
+    /// assigned to.
+    Constant(Slot),
+    Function(Rc<Function>),
+}
+
+/// How a variable's value is stored: as the `Value` itself, or as its 8-byte
+/// NaN-boxed encoding with the `nan-boxing` feature.
+#[cfg(not(feature = "nan-boxing"))]
+pub type Slot = Value;
+#[cfg(feature = "nan-boxing")]
+pub type Slot = NanBox;
+
+impl Symbol {
+    pub fn variable(value: Value) -> Symbol {
+        #[cfg(feature = "nan-boxing")]
+        let value = NanBox::from(value);
+        Symbol::Variable(value)
+    }
+
+    pub fn constant(value: Value) -> Symbol {
+        #[cfg(feature = "nan-boxing")]
+        let value = NanBox::from(value);
+        Symbol::Constant(value)
+    }
+}
+
+#[cfg(not(feature = "nan-boxing"))]
+pub fn unpack(slot: &Slot) -> Value {
+    slot.clone()
+}
+
+#[cfg(feature = "nan-boxing")]
+pub fn unpack(slot: &Slot) -> Value {
+    Value::from(slot)
+}
+
+pub struct Function {
+    pub name: String,
+    pub params: Vec<Token>,
+    pub body: Statement,
+    pub closure: Env,
+}
+
+impl Function {
+    pub fn new(name: String, params: Vec<Token>, body: Statement, closure: Env) -> Rc<Function> {
+        let function = Rc::new(Function {
+            name,
+            params,
+            body,
+            closure,
+        });
+        gc::manage(&function);
+        function
+    }
+}
+
+// Functions have no meaningful structural equality, so two function values
+// are equal only if they're the same function.
+impl PartialEq for Function {
+    fn eq(&self, other: &Function) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+// Closures and environments point at each other, so neither can print the
+// other without recursing forever.
+impl fmt::Debug for Function {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "<fn {}>", self.name)
+    }
+}
+
+impl fmt::Debug for Environment {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Environment")
+            .field("symbols", &self.symbols.keys().collect::<Vec<_>>())
+            .field("enclosing", &self.enclosing.is_some())
+            .finish()
+    }
 }
 
 impl Environment {
     pub fn new() -> Env {
-        Rc::new(RefCell::new(Environment {
-            symbols: HashMap::new(),
-            enclosing: None,
-        }))
+        Self::allocate(None)
     }
 
     pub fn with_enclosing(enclosing: Env) -> Env {
-        Rc::new(RefCell::new(Environment {
+        Self::allocate(Some(enclosing))
+    }
+
+    fn allocate(enclosing: Option<Env>) -> Env {
+        let env = Rc::new(RefCell::new(Environment {
             symbols: HashMap::new(),
-            enclosing: Some(enclosing),
-        }))
+            enclosing,
+        }));
+        gc::manage(&env);
+        env
     }
 
     pub fn define(&mut self, name: String, value: Symbol) {
@@ -50,17 +135,68 @@ impl Environment {
             .and_then(|parent| parent.borrow().get(name))
     }
 
-    pub fn assign(&mut self, name: &str, value: Symbol) -> Result<(), Error> {
-        if self.symbols.contains_key(name) {
-            self.symbols.insert(name.to_string(), value);
-            Ok(())
-        } else if let Some(parent) = self.enclosing.as_ref() {
-            parent.borrow_mut().assign(name, value)
-        } else {
-            Err(Error::RuntimeError(
-                1,
-                format!("Undefined variable '{}'", name),
-            ))
+    /// Overwrites an existing variable. Constants and functions can't be
+    /// assigned to, though a new declaration can still shadow them.
+    pub fn assign(&mut self, name: &str, value: Symbol, line: usize) -> Result<(), Error> {
+        match self.symbols.get(name) {
+            Some(Symbol::Variable(_)) => {
+                self.symbols.insert(name.to_string(), value);
+                Ok(())
+            }
+            Some(Symbol::Constant(_)) => Err(Error::RuntimeError(
+                line,
+                format!("Can't assign to constant '{}'.", name),
+            )),
+            Some(Symbol::Function(_)) => Err(Error::RuntimeError(
+                line,
+                format!("Can't assign to function '{}'.", name),
+            )),
+            None => match self.enclosing.as_ref() {
+                Some(parent) => parent.borrow_mut().assign(name, value, line),
+                None => Err(Error::RuntimeError(
+                    line,
+                    format!("Undefined variable '{}'", name),
+                )),
+            },
+        }
+    }
+}
+
+impl Trace for RefCell<Environment> {
+    fn trace(&self, tracer: &mut Tracer) {
+        let Ok(env) = self.try_borrow() else {
+            return tracer.busy();
+        };
+
+        if let Some(enclosing) = env.enclosing.as_ref() {
+            tracer.visit(enclosing);
+        }
+        for symbol in env.symbols.values() {
+            symbol.trace(tracer);
         }
     }
+
+    fn clear(&self) {
+        if let Ok(mut env) = self.try_borrow_mut() {
+            env.symbols.clear();
+            env.enclosing = None;
+        }
+    }
+}
+
+impl Trace for Symbol {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Symbol::Variable(slot) | Symbol::Constant(slot) => unpack(slot).trace(tracer),
+            Symbol::Function(function) => tracer.visit(function),
+        }
+    }
+}
+
+// Every cycle through a closure also runs through the environment it
+// captured, so clearing environments is enough to break it.
+impl Trace for Function {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.visit(&self.closure);
+    }
 }
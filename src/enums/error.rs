@@ -1,18 +1,27 @@
 use std::fmt::{self, Display};
 
+use crate::enums::token::Span;
+use crate::evaluator::Value;
+
 #[derive(Debug)]
 pub enum Error {
-    ParseError(usize, String),
+    /// A syntax error, with the span of the token it was found at, or
+    /// `None` if the input ended first.
+    ParseError(usize, String, Option<Span>),
     RuntimeError(usize, String),
+    /// A mistake found by the resolver before the program runs.
+    CompileError(usize, String),
+    /// A value raised by a `throw` statement, with the line it was thrown on.
+    Thrown(Value, usize),
 }
 
 impl Display for Error {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> fmt::Result {
         let error = match self {
-            Error::RuntimeError(error, line) => format!("{}/n[line {}]", error, line),
-            Error::ParseError(usize, token) => {
-                format!("[line {}] Error at '{}': Expect expression.", usize, token)
-            }
+            Error::RuntimeError(line, error) => format!("{}\n[line {}]", error, line),
+            Error::CompileError(line, error) => format!("[line {}] Error: {}", line, error),
+            Error::Thrown(value, line) => format!("Uncaught {}\n[line {}]", value, line),
+            Error::ParseError(line, error, _) => format!("[line {}] Error: {}", line, error),
         };
         write!(fmt, "{}", error)
     }
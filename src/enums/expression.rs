@@ -1,13 +1,44 @@
 use std::fmt::{self, Display};
 
+use crate::enums::statement::Statement;
 use crate::enums::token::{Token, format_number};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expression {
-    Binary(Box<Expression>, Operator, Box<Expression>),
-    Unary(Unary, Box<Expression>),
+    /// The line is the operator's.
+    Binary(Box<Expression>, Operator, Box<Expression>, usize),
+    Unary(Unary, Box<Expression>, usize),
     Primary(Primary),
-    Assignment(Primary, Box<Expression>),
+    Assignment(Primary, Box<Expression>, usize),
+    /// `condition ? then : else`; only the chosen branch is evaluated.
+    Conditional(Box<Expression>, Box<Expression>, Box<Expression>),
+    Interpolation(Vec<Expression>),
+    /// An anonymous function, from `fun (a) { ... }` or `(a) => expr`. The
+    /// arrow form's body is a single `return`.
+    Lambda(Vec<Token>, Box<Statement>),
+    /// A call on anything other than a plain name, such as `make()(1)`.
+    Call(Box<Expression>, Vec<Expression>, usize),
+    List(Vec<Expression>),
+    Map(Vec<(Expression, Expression)>, usize),
+    Index(Box<Expression>, Box<Expression>, usize),
+    /// `object.name`, which reads the string key `name` from a map. Imported
+    /// modules are maps, so this is how their exports are reached.
+    Get(Box<Expression>, String, usize),
+    IndexAssignment(Box<Expression>, Box<Expression>, Box<Expression>, usize),
+    /// `target op= value`, where the target is a variable or an index
+    /// expression and is only evaluated once.
+    CompoundAssignment(Box<Expression>, Operator, Box<Expression>, usize),
+    /// `++` or `--` on a variable or index expression, carried as `Plus` or
+    /// `Minus`.
+    Increment(Box<Expression>, Operator, Fixity, usize),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Fixity {
+    /// `++x` evaluates to the updated value.
+    Prefix,
+    /// `x++` evaluates to the value before the update.
+    Postfix,
 }
 
 impl Display for Expression {
@@ -16,15 +47,66 @@ impl Display for Expression {
             Expression::Primary(literal) => {
                 write!(f, "{}", literal)
             }
-            Expression::Unary(unary, expr) => {
+            Expression::Unary(unary, expr, _) => {
                 write!(f, "({} {})", unary, expr)
             }
-            Expression::Binary(left, op, right) => {
+            Expression::Binary(left, op, right, _) => {
                 write!(f, "({} {} {})", op, left, right)
             }
-            Expression::Assignment(identififer, assignment) => {
+            Expression::Assignment(identififer, assignment, _) => {
                 write!(f, "{} = {}", identififer, assignment)
             }
+            Expression::Conditional(condition, then, otherwise) => {
+                write!(f, "(? {} {} {})", condition, then, otherwise)
+            }
+            Expression::Lambda(params, _) => {
+                let params = params
+                    .iter()
+                    .map(|x| x.get_identifier())
+                    .collect::<Vec<String>>()
+                    .join(" ");
+                write!(f, "(lambda ({}))", params)
+            }
+            Expression::Call(callee, args, _) => {
+                let args = args.iter().map(|x| format!(" {}", x)).collect::<String>();
+                write!(f, "(call {}{})", callee, args)
+            }
+            Expression::Interpolation(parts) => {
+                let parts = parts.iter().map(|x| format!(" {}", x)).collect::<String>();
+                write!(f, "(interpolate{})", parts)
+            }
+            Expression::List(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|x| format!(" {}", x))
+                    .collect::<String>();
+                write!(f, "(list{})", elements)
+            }
+            Expression::Map(entries, _) => {
+                let entries = entries
+                    .iter()
+                    .map(|(key, value)| format!(" {} {}", key, value))
+                    .collect::<String>();
+                write!(f, "(map{})", entries)
+            }
+            Expression::Index(list, index, _) => {
+                write!(f, "(index {} {})", list, index)
+            }
+            Expression::Get(object, name, _) => {
+                write!(f, "(get {} {})", object, name)
+            }
+            Expression::IndexAssignment(list, index, value, _) => {
+                write!(f, "{}[{}] = {}", list, index, value)
+            }
+            Expression::CompoundAssignment(target, operator, value, _) => {
+                write!(f, "{} {}= {}", target, operator, value)
+            }
+            Expression::Increment(target, operator, Fixity::Prefix, _) => {
+                write!(f, "({}{} {})", operator, operator, target)
+            }
+            Expression::Increment(target, operator, Fixity::Postfix, _) => {
+                write!(f, "({} {}{})", target, operator, operator)
+            }
         }
     }
 }
@@ -37,8 +119,8 @@ pub enum Primary {
     False,
     Nil,
     Grouping(Box<Expression>),
-    Identifier(String),
-    Function(String, Vec<Expression>),
+    Identifier(String, usize),
+    Function(String, Vec<Expression>, usize),
 }
 
 impl Display for Primary {
@@ -49,8 +131,8 @@ impl Display for Primary {
             Primary::True => "true".to_string(),
             Primary::False => "false".to_string(),
             Primary::Nil => "nil".to_string(),
-            Primary::Identifier(name) => name.to_string(),
-            Primary::Function(name, tokens) => {
+            Primary::Identifier(name, _) => name.to_string(),
+            Primary::Function(name, tokens, _) => {
                 let params = tokens
                     .iter()
                     .map(|x| x.to_string())
@@ -92,10 +174,19 @@ pub enum Operator {
     GreaterEqual,
     Division,
     Star,
+    /// Floored, so the result has the sign of the divisor: `-7 % 3` is 2.
+    Modulo,
+    /// Right-associative and binds tighter than a unary minus on its left,
+    /// so `-2 ** 2` is -4.
+    Power,
+    /// `~/`: division rounded down to a whole number.
+    IntegerDivision,
     Minus,
     Plus,
     Or,
     And,
+    /// `??`: the right side, evaluated only if the left side is nil.
+    Coalesce,
 }
 
 impl Display for Operator {
@@ -109,10 +200,14 @@ impl Display for Operator {
             Operator::GreaterEqual => ">=",
             Operator::Division => "/",
             Operator::Star => "*",
+            Operator::Modulo => "%",
+            Operator::Power => "**",
+            Operator::IntegerDivision => "~/",
             Operator::Plus => "+",
             Operator::Minus => "-",
             Operator::And => "And",
             Operator::Or => "Or",
+            Operator::Coalesce => "??",
         };
 
         write!(fmt, "{}", operator)
@@ -0,0 +1,119 @@
+use std::cell::{Ref, RefCell, RefMut};
+use std::fmt::{self, Display};
+use std::rc::Rc;
+
+use crate::enums::error::Error;
+use crate::evaluator::Value;
+use crate::gc::{self, Trace, Tracer};
+
+thread_local! {
+    // Collections being printed, so one that contains itself prints as `[...]`.
+    static PRINTING: RefCell<Vec<*const ()>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Runs `print` for the collection at `ptr`, or returns `None` if that
+/// collection is already being printed further up.
+pub fn print_nested(ptr: *const (), print: impl FnOnce() -> String) -> Option<String> {
+    if PRINTING.with(|printing| printing.borrow().contains(&ptr)) {
+        return None;
+    }
+
+    PRINTING.with(|printing| printing.borrow_mut().push(ptr));
+    let output = print();
+    PRINTING.with(|printing| printing.borrow_mut().pop());
+    Some(output)
+}
+
+/// Formats a value inside a collection, where strings are quoted.
+pub fn repr(value: &Value) -> String {
+    match value {
+        Value::String(string) => format!("{:?}", string),
+        value => value.to_string(),
+    }
+}
+
+/// A mutable, shared Lox list. Two lists are equal only if they're the same
+/// list.
+#[derive(Clone)]
+pub struct List(pub Rc<RefCell<Vec<Value>>>);
+
+impl List {
+    pub fn new(values: Vec<Value>) -> List {
+        let list = Rc::new(RefCell::new(values));
+        gc::manage(&list);
+        List(list)
+    }
+
+    pub fn borrow(&self) -> Ref<'_, Vec<Value>> {
+        self.0.borrow()
+    }
+
+    pub fn borrow_mut(&self) -> RefMut<'_, Vec<Value>> {
+        self.0.borrow_mut()
+    }
+
+    /// Checks that `index` is a whole number below `len`.
+    pub fn position(index: &Value, len: usize, line: usize) -> Result<usize, Error> {
+        let index = match index {
+            Value::Number(index) if index.fract() == 0.0 => *index,
+            _ => {
+                let error = format!("Index must be an integer, got {}.", index);
+                return Err(Error::RuntimeError(line, error));
+            }
+        };
+
+        if index < 0.0 || index >= len as f64 {
+            let error = format!("Index {} out of bounds for length {}.", index, len);
+            return Err(Error::RuntimeError(line, error));
+        }
+
+        Ok(index as usize)
+    }
+}
+
+impl PartialEq for List {
+    fn eq(&self, other: &List) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Display for List {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let values = print_nested(Rc::as_ptr(&self.0) as *const (), || {
+            self.borrow()
+                .iter()
+                .map(repr)
+                .collect::<Vec<String>>()
+                .join(", ")
+        });
+
+        match values {
+            Some(values) => write!(fmt, "[{}]", values),
+            None => write!(fmt, "[...]"),
+        }
+    }
+}
+
+impl fmt::Debug for List {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self)
+    }
+}
+
+impl Trace for RefCell<Vec<Value>> {
+    fn trace(&self, tracer: &mut Tracer) {
+        let Ok(values) = self.try_borrow() else {
+            return tracer.busy();
+        };
+
+        for value in values.iter() {
+            value.trace(tracer);
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut values) = self.try_borrow_mut() {
+            values.clear();
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::hash::{Hash, Hasher};
+use std::rc::{Rc, Weak};
+
+thread_local! {
+    // Weak, so an interned string goes away with its last use; its buffer
+    // removes its entry when it's dropped.
+    static INTERNED: RefCell<HashMap<String, Weak<Buffer>>> = RefCell::new(HashMap::new());
+}
+
+/// An immutable, reference-counted Lox string.
+///
+/// Every string is a prefix of a shared buffer. Concatenating onto a string
+/// that ends where its buffer ends appends in place and hands back a longer
+/// view, which leaves the original (shorter) view untouched. That makes
+/// `s = s + x` in a loop linear instead of quadratic, while a second
+/// concatenation onto the same `s` falls back to copying.
+#[derive(Clone)]
+pub struct LoxString {
+    buffer: Rc<Buffer>,
+    len: usize,
+}
+
+struct Buffer {
+    text: RefCell<String>,
+    // Interned buffers are unique per content and never appended to, so two
+    // interned strings are equal exactly when they share a buffer.
+    interned: bool,
+}
+
+impl LoxString {
+    pub fn new(text: String) -> LoxString {
+        let len = text.len();
+        let buffer = Rc::new(Buffer {
+            text: RefCell::new(text),
+            interned: false,
+        });
+        LoxString { buffer, len }
+    }
+
+    /// Returns the shared copy of `text`, creating it if no string holds it.
+    pub fn intern(text: &str) -> LoxString {
+        let buffer = INTERNED.with(|interned| {
+            let mut interned = interned.borrow_mut();
+            if let Some(buffer) = interned.get(text).and_then(Weak::upgrade) {
+                return buffer;
+            }
+            let buffer = Rc::new(Buffer {
+                text: RefCell::new(text.to_string()),
+                interned: true,
+            });
+            interned.insert(text.to_string(), Rc::downgrade(&buffer));
+            buffer
+        });
+        LoxString {
+            buffer,
+            len: text.len(),
+        }
+    }
+
+    pub fn as_str(&self) -> Ref<'_, str> {
+        Ref::map(self.buffer.text.borrow(), |text| &text[..self.len])
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn concat(&self, other: &LoxString) -> LoxString {
+        if !self.buffer.interned && self.buffer.text.borrow().len() == self.len {
+            let len = self.len + other.len;
+            // `other` may be a view of this very buffer, so copy it out first.
+            let suffix = other.as_str().to_string();
+            self.buffer.text.borrow_mut().push_str(&suffix);
+            return LoxString {
+                buffer: self.buffer.clone(),
+                len,
+            };
+        }
+
+        let mut text = String::with_capacity(self.len + other.len);
+        text.push_str(&self.as_str());
+        text.push_str(&other.as_str());
+        LoxString::new(text)
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        if !self.interned {
+            return;
+        }
+        // The table may be gone already if the thread is exiting.
+        let _ = INTERNED.try_with(|interned| {
+            if let Ok(mut interned) = interned.try_borrow_mut() {
+                let text = self.text.get_mut();
+                if interned
+                    .get(text.as_str())
+                    .is_some_and(|weak| weak.strong_count() == 0)
+                {
+                    interned.remove(text.as_str());
+                }
+            }
+        });
+    }
+}
+
+impl PartialEq for LoxString {
+    fn eq(&self, other: &LoxString) -> bool {
+        if Rc::ptr_eq(&self.buffer, &other.buffer) {
+            return self.len == other.len;
+        }
+        if self.buffer.interned && other.buffer.interned {
+            return false;
+        }
+        self.len == other.len && *self.as_str() == *other.as_str()
+    }
+}
+
+impl Eq for LoxString {}
+
+impl Hash for LoxString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl From<String> for LoxString {
+    fn from(text: String) -> LoxString {
+        LoxString::new(text)
+    }
+}
+
+impl From<&str> for LoxString {
+    fn from(text: &str) -> LoxString {
+        LoxString::new(text.to_string())
+    }
+}
+
+impl Display for LoxString {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", &*self.as_str())
+    }
+}
+
+impl fmt::Debug for LoxString {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{:?}", &*self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interned_strings_share_a_buffer() {
+        let first = LoxString::intern("name");
+        let second = LoxString::intern("name");
+        assert!(Rc::ptr_eq(&first.buffer, &second.buffer));
+        assert_eq!(first, second);
+        assert_ne!(first, LoxString::intern("other"));
+    }
+
+    #[test]
+    fn appending_to_the_end_of_a_buffer_is_in_place() {
+        let start = LoxString::from("ab");
+        let longer = start.concat(&LoxString::from("cd"));
+        assert!(Rc::ptr_eq(&start.buffer, &longer.buffer));
+        assert_eq!(start.to_string(), "ab");
+        assert_eq!(longer.to_string(), "abcd");
+
+        // `start` no longer ends its buffer, so this one has to copy.
+        let other = start.concat(&LoxString::from("xy"));
+        assert!(!Rc::ptr_eq(&start.buffer, &other.buffer));
+        assert_eq!(other.to_string(), "abxy");
+        assert_eq!(longer.to_string(), "abcd");
+    }
+
+    #[test]
+    fn interned_strings_are_never_appended_to() {
+        let interned = LoxString::intern("fixed");
+        let longer = interned.concat(&LoxString::from("!"));
+        assert!(!Rc::ptr_eq(&interned.buffer, &longer.buffer));
+        assert_eq!(LoxString::intern("fixed").to_string(), "fixed");
+    }
+
+    #[test]
+    fn interned_strings_leave_the_table_with_their_last_use() {
+        let interned = |text: &str| INTERNED.with(|table| table.borrow().contains_key(text));
+
+        let first = LoxString::intern("passing");
+        let second = first.clone();
+        drop(first);
+        assert!(interned("passing"));
+        drop(second);
+        assert!(!interned("passing"));
+
+        // Interning it again starts a new entry.
+        let again = LoxString::intern("passing");
+        assert!(interned("passing"));
+        assert_eq!(again.to_string(), "passing");
+    }
+
+    #[test]
+    fn a_string_can_be_appended_to_itself() {
+        let string = LoxString::from("ab");
+        assert_eq!(string.concat(&string).to_string(), "abab");
+    }
+}
@@ -0,0 +1,190 @@
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::rc::Rc;
+
+use crate::enums::error::Error;
+use crate::enums::list::{print_nested, repr};
+use crate::enums::lox_string::LoxString;
+use crate::evaluator::Value;
+use crate::gc::{self, Trace, Tracer};
+
+/// A mutable, shared Lox map. Like lists, two maps are equal only if they're
+/// the same map.
+#[derive(Clone)]
+pub struct Map(pub Rc<RefCell<OrderedMap>>);
+
+/// A hash map that remembers insertion order, so iterating a map is
+/// deterministic.
+#[derive(Default)]
+pub struct OrderedMap {
+    entries: Vec<(Key, Value)>,
+    positions: HashMap<Key, usize>,
+    // Entries that can't be assigned to or removed, with what they are.
+    fixed: HashMap<Key, &'static str>,
+}
+
+/// The values that can be used as map keys.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    String(LoxString),
+    // Compared by bits, with -0 folded into 0 and NaN rejected.
+    Number(u64),
+    Boolean(bool),
+    Nil,
+}
+
+impl Key {
+    pub fn new(value: &Value, line: usize) -> Result<Key, Error> {
+        match value {
+            Value::String(string) => Ok(Key::String(string.clone())),
+            Value::Number(number) if number.is_nan() => Err(Error::RuntimeError(
+                line,
+                "Map key can't be NaN.".to_string(),
+            )),
+            Value::Number(number) => Ok(Key::Number((number + 0.0).to_bits())),
+            Value::Boolean(bool) => Ok(Key::Boolean(*bool)),
+            Value::Nil => Ok(Key::Nil),
+            _ => Err(Error::RuntimeError(
+                line,
+                "Map keys must be strings, numbers, booleans or nil.".to_string(),
+            )),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            Key::String(string) => Value::String(string.clone()),
+            Key::Number(bits) => Value::Number(f64::from_bits(*bits)),
+            Key::Boolean(bool) => Value::Boolean(*bool),
+            Key::Nil => Value::Nil,
+        }
+    }
+}
+
+impl OrderedMap {
+    pub fn get(&self, key: &Key) -> Option<&Value> {
+        self.positions.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    pub fn insert(&mut self, key: Key, value: Value) {
+        match self.positions.get(&key) {
+            Some(&i) => self.entries[i].1 = value,
+            None => {
+                self.positions.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    /// Inserts an entry that can never be assigned to or removed, like a
+    /// module's constants and functions. `kind` names it in errors.
+    pub fn insert_fixed(&mut self, key: Key, value: Value, kind: &'static str) {
+        self.insert(key.clone(), value);
+        self.fixed.insert(key, kind);
+    }
+
+    /// What the entry at `key` is, if it's fixed.
+    pub fn fixed(&self, key: &Key) -> Option<&'static str> {
+        self.fixed.get(key).copied()
+    }
+
+    /// Fails if the entry at `key` is fixed.
+    pub fn check_writable(&self, key: &Key, line: usize) -> Result<(), Error> {
+        match self.fixed(key) {
+            Some(kind) => Err(Error::RuntimeError(
+                line,
+                format!("Can't assign to {} '{}'.", kind, key.to_value()),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    pub fn remove(&mut self, key: &Key) -> Option<Value> {
+        let i = self.positions.remove(key)?;
+        let (_, value) = self.entries.remove(i);
+        for (key, _) in &self.entries[i..] {
+            if let Some(position) = self.positions.get_mut(key) {
+                *position -= 1;
+            }
+        }
+        Some(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(Key, Value)> {
+        self.entries.iter()
+    }
+}
+
+impl Map {
+    pub fn new(map: OrderedMap) -> Map {
+        let map = Rc::new(RefCell::new(map));
+        gc::manage(&map);
+        Map(map)
+    }
+
+    pub fn borrow(&self) -> Ref<'_, OrderedMap> {
+        self.0.borrow()
+    }
+
+    pub fn borrow_mut(&self) -> RefMut<'_, OrderedMap> {
+        self.0.borrow_mut()
+    }
+}
+
+impl PartialEq for Map {
+    fn eq(&self, other: &Map) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Display for Map {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let entries = print_nested(Rc::as_ptr(&self.0) as *const (), || {
+            self.borrow()
+                .iter()
+                .map(|(key, value)| format!("{}: {}", repr(&key.to_value()), repr(value)))
+                .collect::<Vec<String>>()
+                .join(", ")
+        });
+
+        match entries {
+            Some(entries) => write!(fmt, "{{{}}}", entries),
+            None => write!(fmt, "{{...}}"),
+        }
+    }
+}
+
+impl fmt::Debug for Map {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self)
+    }
+}
+
+impl Trace for RefCell<OrderedMap> {
+    fn trace(&self, tracer: &mut Tracer) {
+        let Ok(map) = self.try_borrow() else {
+            return tracer.busy();
+        };
+
+        for (_, value) in map.iter() {
+            value.trace(tracer);
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut map) = self.try_borrow_mut() {
+            map.entries.clear();
+            map.positions.clear();
+            map.fixed.clear();
+        }
+    }
+}
@@ -1,5 +1,11 @@
 pub mod environment;
 pub mod error;
 pub mod expression;
+pub mod list;
+pub mod lox_string;
+pub mod map;
+#[cfg(feature = "nan-boxing")]
+pub mod nan_box;
+pub mod range;
 pub mod statement;
 pub mod token;
@@ -0,0 +1,235 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::enums::environment::Function;
+use crate::enums::list::List;
+use crate::enums::lox_string::LoxString;
+use crate::enums::map::{Map, OrderedMap};
+use crate::enums::range::Range;
+use crate::evaluator::Value;
+
+// Any f64 that isn't a quiet NaN with these bits set is stored as is. The
+// rest of the quiet NaN space holds the other variants: nil and the booleans
+// as small constants, and heap values as a pointer in the low 48 bits with
+// the sign bit set. Heap pointers are at least 8-byte aligned, which leaves
+// the bottom 3 bits free to say what they point at.
+const QNAN: u64 = 0x7ffc_0000_0000_0000;
+const SIGN_BIT: u64 = 0x8000_0000_0000_0000;
+const POINTER: u64 = SIGN_BIT | QNAN;
+const ADDRESS_MASK: u64 = 0x0000_ffff_ffff_fff8;
+const TAG_MASK: u64 = 0x7;
+
+const NIL: u64 = QNAN | 1;
+const FALSE: u64 = QNAN | 2;
+const TRUE: u64 = QNAN | 3;
+
+const TAG_STRING: u64 = 0;
+const TAG_LIST: u64 = 1;
+const TAG_MAP: u64 = 2;
+const TAG_RANGE: u64 = 3;
+const TAG_FUNCTION: u64 = 4;
+
+// The pointer tags need 64-bit addresses, of which only the low 48 are in
+// use, and pointees aligned to at least 8 bytes.
+#[cfg(not(target_pointer_width = "64"))]
+compile_error!("the nan-boxing feature needs a 64-bit target");
+
+const _: () = {
+    use std::mem::align_of;
+    assert!(align_of::<LoxString>() >= 8);
+    assert!(align_of::<RefCell<Vec<Value>>>() >= 8);
+    assert!(align_of::<RefCell<OrderedMap>>() >= 8);
+    assert!(align_of::<Range>() >= 8);
+    assert!(align_of::<Function>() >= 8);
+};
+
+/// An 8-byte encoding of a `Value`, used for variable slots with the
+/// `nan-boxing` feature. Only environments store it: expression results,
+/// list elements and map values are still `Value`s.
+///
+/// A `LoxString` is a buffer pointer and a length, too wide for the 48 bits
+/// a box has for a pointer, so a string gets its own `Rc` to point at.
+pub struct NanBox(u64);
+
+impl NanBox {
+    fn is_pointer(&self) -> bool {
+        self.0 & POINTER == POINTER
+    }
+
+    fn address(&self) -> *const () {
+        (self.0 & ADDRESS_MASK) as *const ()
+    }
+
+    fn tag(&self) -> u64 {
+        self.0 & TAG_MASK
+    }
+
+    fn pointer<T>(object: Rc<T>, tag: u64) -> NanBox {
+        let address = Rc::into_raw(object) as u64;
+        // Checked in release builds too: an address with bits outside the
+        // mask would come back out as a different pointer.
+        assert_eq!(address & !ADDRESS_MASK, 0, "pointer doesn't fit a NaN box");
+        NanBox(POINTER | address | tag)
+    }
+}
+
+impl From<Value> for NanBox {
+    fn from(value: Value) -> NanBox {
+        match value {
+            // Arithmetic can produce NaNs with any payload, so they're all
+            // folded into the one that can't be mistaken for a tag.
+            Value::Number(number) if number.is_nan() => NanBox(f64::NAN.to_bits()),
+            Value::Number(number) => NanBox(number.to_bits()),
+            Value::Nil => NanBox(NIL),
+            Value::Boolean(false) => NanBox(FALSE),
+            Value::Boolean(true) => NanBox(TRUE),
+            Value::String(string) => NanBox::pointer(Rc::new(string), TAG_STRING),
+            Value::List(list) => NanBox::pointer(list.0, TAG_LIST),
+            Value::Map(map) => NanBox::pointer(map.0, TAG_MAP),
+            Value::Range(range) => NanBox::pointer(range, TAG_RANGE),
+            Value::Function(function) => NanBox::pointer(function, TAG_FUNCTION),
+        }
+    }
+}
+
+impl From<&NanBox> for Value {
+    fn from(boxed: &NanBox) -> Value {
+        match boxed.0 {
+            NIL => Value::Nil,
+            FALSE => Value::Boolean(false),
+            TRUE => Value::Boolean(true),
+            _ if boxed.is_pointer() => match boxed.tag() {
+                TAG_STRING => {
+                    // Safety: the address came from `Rc::into_raw` on a
+                    // `LoxString` and this box still owns that reference.
+                    let string = unsafe { &*(boxed.address() as *const LoxString) };
+                    Value::String(string.clone())
+                }
+                TAG_LIST => {
+                    let list = boxed.address() as *const RefCell<Vec<Value>>;
+                    // Safety: as above, for the list's `Rc`.
+                    unsafe { Rc::increment_strong_count(list) };
+                    Value::List(List(unsafe { Rc::from_raw(list) }))
+                }
+                TAG_MAP => {
+                    let map = boxed.address() as *const RefCell<OrderedMap>;
+                    // Safety: as above, for the map's `Rc`.
+                    unsafe { Rc::increment_strong_count(map) };
+                    Value::Map(Map(unsafe { Rc::from_raw(map) }))
+                }
+                TAG_RANGE => {
+                    let range = boxed.address() as *const Range;
+                    // Safety: as above, for the range's `Rc`.
+                    unsafe { Rc::increment_strong_count(range) };
+                    Value::Range(unsafe { Rc::from_raw(range) })
+                }
+                TAG_FUNCTION => {
+                    let function = boxed.address() as *const Function;
+                    // Safety: as above, for the function's `Rc`.
+                    unsafe { Rc::increment_strong_count(function) };
+                    Value::Function(unsafe { Rc::from_raw(function) })
+                }
+                tag => unreachable!("unknown NaN box tag {}", tag),
+            },
+            bits => Value::Number(f64::from_bits(bits)),
+        }
+    }
+}
+
+impl Clone for NanBox {
+    fn clone(&self) -> NanBox {
+        if self.is_pointer() {
+            match self.tag() {
+                // Safety: see `From<&NanBox> for Value`.
+                TAG_STRING => unsafe {
+                    Rc::increment_strong_count(self.address() as *const LoxString)
+                },
+                TAG_LIST => unsafe {
+                    Rc::increment_strong_count(self.address() as *const RefCell<Vec<Value>>)
+                },
+                TAG_MAP => unsafe {
+                    Rc::increment_strong_count(self.address() as *const RefCell<OrderedMap>)
+                },
+                TAG_RANGE => unsafe { Rc::increment_strong_count(self.address() as *const Range) },
+                TAG_FUNCTION => unsafe {
+                    Rc::increment_strong_count(self.address() as *const Function)
+                },
+                tag => unreachable!("unknown NaN box tag {}", tag),
+            }
+        }
+        NanBox(self.0)
+    }
+}
+
+impl Drop for NanBox {
+    fn drop(&mut self) {
+        if self.is_pointer() {
+            match self.tag() {
+                // Safety: see `From<&NanBox> for Value`.
+                TAG_STRING => unsafe {
+                    Rc::decrement_strong_count(self.address() as *const LoxString)
+                },
+                TAG_LIST => unsafe {
+                    Rc::decrement_strong_count(self.address() as *const RefCell<Vec<Value>>)
+                },
+                TAG_MAP => unsafe {
+                    Rc::decrement_strong_count(self.address() as *const RefCell<OrderedMap>)
+                },
+                TAG_RANGE => unsafe { Rc::decrement_strong_count(self.address() as *const Range) },
+                TAG_FUNCTION => unsafe {
+                    Rc::decrement_strong_count(self.address() as *const Function)
+                },
+                tag => unreachable!("unknown NaN box tag {}", tag),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for NanBox {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "NanBox({:?})", Value::from(self))
+    }
+}
+
+const _: () = assert!(std::mem::size_of::<NanBox>() == 8);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(value: Value) -> Value {
+        Value::from(&NanBox::from(value))
+    }
+
+    #[test]
+    fn immediates_round_trip() {
+        for value in [
+            Value::Nil,
+            Value::Boolean(true),
+            Value::Boolean(false),
+            Value::Number(0.0),
+            Value::Number(-1.5),
+            Value::Number(f64::INFINITY),
+        ] {
+            assert_eq!(round_trip(value.clone()), value);
+        }
+        assert!(matches!(round_trip(Value::Number(f64::NAN)), Value::Number(n) if n.is_nan()));
+    }
+
+    #[test]
+    fn heap_values_round_trip_and_keep_their_count() {
+        let string = Value::String(LoxString::from("boxed"));
+        assert_eq!(round_trip(string.clone()), string);
+
+        let list = List::new(vec![Value::Number(1.0)]);
+        let boxed = NanBox::from(Value::List(list.clone()));
+        assert_eq!(Rc::strong_count(&list.0), 2);
+        let copy = boxed.clone();
+        assert_eq!(Rc::strong_count(&list.0), 3);
+        assert_eq!(Value::from(&copy), Value::List(list.clone()));
+        drop(boxed);
+        drop(copy);
+        assert_eq!(Rc::strong_count(&list.0), 1);
+    }
+}
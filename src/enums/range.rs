@@ -0,0 +1,39 @@
+use std::fmt::{self, Display};
+
+/// The numbers from `start` up to, but not including, `end`, counting by
+/// `step`. A negative step counts down instead.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Range {
+    pub start: f64,
+    pub end: f64,
+    pub step: f64,
+}
+
+impl Range {
+    pub fn iter(self) -> impl Iterator<Item = f64> {
+        (0..)
+            .map(move |i| self.start + i as f64 * self.step)
+            .take_while(move |&n| {
+                if self.step > 0.0 {
+                    n < self.end
+                } else {
+                    n > self.end
+                }
+            })
+    }
+
+    pub fn len(&self) -> usize {
+        let len = ((self.end - self.start) / self.step).ceil();
+        if len > 0.0 { len as usize } else { 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Display for Range {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "range({}, {}, {})", self.start, self.end, self.step)
+    }
+}
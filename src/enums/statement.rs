@@ -3,10 +3,12 @@ use std::fmt::{self, Display};
 use crate::enums::expression::Expression;
 use crate::enums::token::Token;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Statement {
     Block(Vec<Statement>),
-    Declaration(String, Expression),
+    /// The last field of a declaration is its `///` doc comment.
+    Declaration(String, Expression, Option<String>),
+    Const(String, Expression, Option<String>),
     Expression(Expression),
     IfElse(Expression, Box<Statement>, Option<Box<Statement>>),
     Print(Expression),
@@ -17,8 +19,19 @@ pub enum Statement {
         Option<Expression>,
         Box<Statement>,
     ),
-    Fn(String, Vec<Token>, Box<Statement>),
+    ForIn(String, Expression, Box<Statement>, usize),
+    Fn(String, Vec<Token>, Box<Statement>, Option<String>),
     Return(Expression),
+    Throw(Expression, usize),
+    /// `import "path";` or `import "path" as name;`.
+    Import(String, Option<String>, usize),
+    /// A try block with an optional `catch (name)` handler and an optional
+    /// `finally` block. At least one of the two is always present.
+    Try(
+        Box<Statement>,
+        Option<(String, Box<Statement>)>,
+        Option<Box<Statement>>,
+    ),
 }
 
 impl Display for Statement {
@@ -27,9 +40,12 @@ impl Display for Statement {
             Statement::Block(statements) => write!(fmt, "{:?}", statements),
             Statement::Print(expression) => write!(fmt, "{}", expression),
             Statement::Expression(expression) => write!(fmt, "{}", expression),
-            Statement::Declaration(string, expression) => {
+            Statement::Declaration(string, expression, _) => {
                 write!(fmt, "{} - {}", string, expression)
             }
+            Statement::Const(string, expression, _) => {
+                write!(fmt, "const {} - {}", string, expression)
+            }
             Statement::IfElse(conditional, if_stmt, _) => {
                 write!(fmt, "if ({}) {} else ", conditional, if_stmt,)
             }
@@ -46,7 +62,10 @@ impl Display for Statement {
                     block
                 )
             }
-            Statement::Fn(name, params, body) => {
+            Statement::ForIn(name, iterable, block, _) => {
+                write!(fmt, "for ({} in {}) {} ", name, iterable, block)
+            }
+            Statement::Fn(name, params, body, _) => {
                 let params = params
                     .iter()
                     .map(|x| x.to_string())
@@ -56,6 +75,21 @@ impl Display for Statement {
                 write!(fmt, "{}({}) {}", name, params, body)
             }
             Statement::Return(expr) => write!(fmt, "return {}", expr),
+            Statement::Throw(expr, _) => write!(fmt, "throw {}", expr),
+            Statement::Import(path, alias, _) => match alias {
+                Some(alias) => write!(fmt, "import \"{}\" as {}", path, alias),
+                None => write!(fmt, "import \"{}\"", path),
+            },
+            Statement::Try(body, catch, finally) => {
+                write!(fmt, "try {}", body)?;
+                if let Some((name, handler)) = catch {
+                    write!(fmt, " catch ({}) {}", name, handler)?;
+                }
+                if let Some(finally) = finally {
+                    write!(fmt, " finally {}", finally)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -19,6 +19,10 @@ impl TokenStream {
         self.tokens.front().map(|t| &t.token)
     }
 
+    pub fn peek_nth(&self, n: usize) -> Option<&Token> {
+        self.tokens.get(n).map(|t| &t.token)
+    }
+
     pub fn advance(&mut self) -> Option<Token> {
         self.tokens.pop_front().map(|t| t.token)
     }
@@ -46,16 +50,27 @@ impl TokenStream {
         if self.peek_is(expected) {
             Ok(self.advance().unwrap())
         } else {
-            Err(Error::ParseError(self.current_line(), message.to_string()))
+            Err(self.error(message))
         }
     }
 
-    pub fn current_line(&mut self) -> usize {
-        if let Some(token) = self.tokens.pop_front() {
-            return token.line_number;
-        }
+    /// The doc comment on the next token, if it has one.
+    pub fn doc(&self) -> Option<String> {
+        self.tokens.front().and_then(|t| t.doc.clone())
+    }
+
+    pub fn line(&self) -> usize {
+        self.tokens.front().map_or(0, |t| t.line_number)
+    }
+
+    /// The span of the next token, or `None` at the end of the input.
+    pub fn span(&self) -> Option<Span> {
+        self.tokens.front().map(|t| t.span)
+    }
 
-        0
+    /// A syntax error at the next token, or at the end of the input.
+    pub fn error(&self, message: impl Into<String>) -> Error {
+        Error::ParseError(self.line(), message.into(), self.span())
     }
 
     pub fn consume_identifier(&mut self, message: &str) -> Result<String, Error> {
@@ -65,15 +80,26 @@ impl TokenStream {
                 self.advance();
                 Ok(name)
             }
-            _ => Err(Error::ParseError(self.current_line(), message.to_string())),
+            _ => Err(self.error(message)),
         }
     }
 }
 
+/// A byte range in the source a token was scanned from.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Lexeme {
     pub token: Token,
     pub line_number: usize,
+    pub span: Span,
+    /// The `///` comment lines right before this token, without their
+    /// slashes.
+    pub doc: Option<String>,
 }
 
 impl Display for Lexeme {
@@ -82,20 +108,41 @@ impl Display for Lexeme {
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub enum StringPart {
+    Text(String),
+    Code(Vec<Lexeme>),
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Token {
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Dot,
     Comma,
     Plus,
+    PlusPlus,
+    PlusEqual,
     Minus,
+    MinusMinus,
+    MinusEqual,
     Star,
+    StarEqual,
+    StarStar,
+    Percent,
+    PercentEqual,
+    TildeSlash,
     SemiColon,
+    Colon,
+    Question,
+    QuestionQuestion,
     Equal,
     EqualEqual,
+    Arrow,
     Bang,
     BangEqual,
     Less,
@@ -103,27 +150,41 @@ pub enum Token {
     Greater,
     GreaterEqual,
     Division,
-    String(String),
+    DivisionEqual,
+    /// The source text between the quotes, and the value after escapes.
+    String(String, String),
+    /// A string with embedded `${...}` expressions, as its source text and
+    /// its parts.
+    Interpolation(String, Vec<StringPart>),
     Identifier(String),
     Number(String, f64),
     Error(char, usize),
     ErrorString(String, usize),
+    LexError(String, usize),
     EOF,
 
     And,
+    As,
+    Catch,
     Class,
+    Const,
     Else,
     False,
+    Finally,
     For,
     Fun,
     If,
+    Import,
+    In,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
 
@@ -142,19 +203,27 @@ impl Token {
 pub static KEYWORD_MAP: Lazy<HashMap<&'static str, Token>> = Lazy::new(|| {
     let mut m = HashMap::new();
     m.insert("and", Token::And);
+    m.insert("as", Token::As);
+    m.insert("catch", Token::Catch);
     m.insert("class", Token::Class);
+    m.insert("const", Token::Const);
     m.insert("else", Token::Else);
     m.insert("false", Token::False);
+    m.insert("finally", Token::Finally);
     m.insert("for", Token::For);
     m.insert("fun", Token::Fun);
     m.insert("if", Token::If);
+    m.insert("import", Token::Import);
+    m.insert("in", Token::In);
     m.insert("nil", Token::Nil);
     m.insert("or", Token::Or);
     m.insert("print", Token::Print);
     m.insert("return", Token::Return);
     m.insert("super", Token::Super);
     m.insert("this", Token::This);
+    m.insert("throw", Token::Throw);
     m.insert("true", Token::True);
+    m.insert("try", Token::Try);
     m.insert("var", Token::Var);
     m.insert("while", Token::While);
     m
@@ -167,14 +236,29 @@ impl Display for Token {
             Self::RightParen => "RIGHT_PAREN ) null".to_string(),
             Self::LeftBrace => "LEFT_BRACE { null".to_string(),
             Self::RightBrace => "RIGHT_BRACE } null".to_string(),
+            Self::LeftBracket => "LEFT_BRACKET [ null".to_string(),
+            Self::RightBracket => "RIGHT_BRACKET ] null".to_string(),
             Self::Comma => "COMMA , null".to_string(),
             Self::Dot => "DOT . null".to_string(),
             Self::Plus => "PLUS + null".to_string(),
+            Self::PlusPlus => "PLUS_PLUS ++ null".to_string(),
+            Self::PlusEqual => "PLUS_EQUAL += null".to_string(),
             Self::Minus => "MINUS - null".to_string(),
+            Self::MinusMinus => "MINUS_MINUS -- null".to_string(),
+            Self::MinusEqual => "MINUS_EQUAL -= null".to_string(),
             Self::Star => "STAR * null".to_string(),
+            Self::StarEqual => "STAR_EQUAL *= null".to_string(),
+            Self::StarStar => "STAR_STAR ** null".to_string(),
+            Self::Percent => "PERCENT % null".to_string(),
+            Self::PercentEqual => "PERCENT_EQUAL %= null".to_string(),
+            Self::TildeSlash => "TILDE_SLASH ~/ null".to_string(),
             Self::SemiColon => "SEMICOLON ; null".to_string(),
+            Self::Colon => "COLON : null".to_string(),
+            Self::Question => "QUESTION ? null".to_string(),
+            Self::QuestionQuestion => "QUESTION_QUESTION ?? null".to_string(),
             Self::Equal => "EQUAL = null".to_string(),
             Self::EqualEqual => "EQUAL_EQUAL == null".to_string(),
+            Self::Arrow => "ARROW => null".to_string(),
             Self::BangEqual => "BANG_EQUAL != null".to_string(),
             Self::Bang => "BANG ! null".to_string(),
             Self::LessEqual => "LESS_EQUAL <= null".to_string(),
@@ -182,11 +266,15 @@ impl Display for Token {
             Self::GreaterEqual => "GREATER_EQUAL >= null".to_string(),
             Self::Greater => "GREATER > null".to_string(),
             Self::Division => "SLASH / null".to_string(),
+            Self::DivisionEqual => "SLASH_EQUAL /= null".to_string(),
             Self::Identifier(identifier) => {
                 format!("IDENTIFIER {} null", &identifier)
             }
-            Self::String(string) => {
-                format!("STRING \"{}\" {}", &string, &string)
+            Self::String(raw, string) => {
+                format!("STRING \"{}\" {}", &raw, &string)
+            }
+            Self::Interpolation(raw, _) => {
+                format!("INTERPOLATION \"{}\" null", &raw)
             }
             Self::Number(string, number) => {
                 format!("NUMBER {} {}", string, format_number(number))
@@ -198,21 +286,32 @@ impl Display for Token {
             Self::ErrorString(_, line) => {
                 format!("[line {}] Error: Unterminated string.", &line)
             }
+            Self::LexError(message, line) => {
+                format!("[line {}] Error: {}", &line, &message)
+            }
             Self::EOF => "EOF null".to_string(),
             Self::And => format!("{} {} null", "AND", "and"),
+            Self::As => format!("{} {} null", "AS", "as"),
+            Self::Catch => format!("{} {} null", "CATCH", "catch"),
             Self::Class => format!("{} {} null", "CLASS", "class"),
+            Self::Const => format!("{} {} null", "CONST", "const"),
             Self::Else => format!("{} {} null", "ELSE", "else"),
             Self::False => format!("{} {} null", "FALSE", "false"),
+            Self::Finally => format!("{} {} null", "FINALLY", "finally"),
             Self::For => format!("{} {} null", "FOR", "for"),
             Self::Fun => format!("{} {} null", "FUN", "fun"),
             Self::If => format!("{} {} null", "IF", "if"),
+            Self::Import => format!("{} {} null", "IMPORT", "import"),
+            Self::In => format!("{} {} null", "IN", "in"),
             Self::Nil => format!("{} {} null", "NIL", "nil"),
             Self::Or => format!("{} {} null", "OR", "or"),
             Self::Print => format!("{} {} null", "PRINT", "print"),
             Self::Return => format!("{} {} null", "RETURN", "return"),
             Self::Super => format!("{} {} null", "SUPER", "super"),
             Self::This => format!("{} {} null", "THIS", "this"),
+            Self::Throw => format!("{} {} null", "THROW", "throw"),
             Self::True => format!("{} {} null", "TRUE", "true"),
+            Self::Try => format!("{} {} null", "TRY", "try"),
             Self::Var => format!("{} {} null", "VAR", "var"),
             Self::While => format!("{} {} null", "WHILE", "while"),
             Self::Unknown => "Unknown token".to_string(),
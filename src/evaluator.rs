@@ -1,19 +1,26 @@
-use crate::enums::environment::{Env, Environment, Symbol};
+use crate::enums::environment::{Env, Environment, Function, Symbol, unpack};
 use crate::enums::error::Error;
-use crate::enums::expression::{Expression, Operator, Primary, Unary};
-use crate::enums::statement::Statement;
-use crate::enums::token::Token;
-use crate::run::{evaluate_statement, ControlFlow};
-use std::collections::VecDeque;
+use crate::enums::expression::{Expression, Fixity, Operator, Primary, Unary};
+use crate::enums::list::{List, repr};
+use crate::enums::lox_string::LoxString;
+use crate::enums::map::{Key, Map, OrderedMap};
+use crate::enums::range::Range;
+use crate::gc::{Trace, Tracer};
+use crate::natives::{call_native, constant, lookup, string};
+use crate::run::{ControlFlow, TailCall, evaluate_statement};
 
 use std::fmt::{self, Display};
-use std::time::SystemTime;
+use std::rc::Rc;
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum Value {
-    String(String),
+    String(LoxString),
     Number(f64),
     Boolean(bool),
+    List(List),
+    Map(Map),
+    Range(Rc<Range>),
+    Function(Rc<Function>),
     Nil,
 }
 
@@ -23,34 +30,298 @@ impl Display for Value {
             Value::String(string) => write!(fmt, "{}", string),
             Value::Number(number) => write!(fmt, "{}", number),
             Value::Boolean(bool) => write!(fmt, "{}", bool),
+            Value::List(list) => write!(fmt, "{}", list),
+            Value::Map(map) => write!(fmt, "{}", map),
+            Value::Range(range) => write!(fmt, "{}", range),
+            Value::Function(function) => write!(fmt, "<fn {}>", function.name),
             Value::Nil => write!(fmt, "nil"),
         }
     }
 }
 
+impl Trace for Value {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Value::List(list) => tracer.visit(&list.0),
+            Value::Map(map) => tracer.visit(&map.0),
+            Value::Function(function) => tracer.visit(function),
+            _ => {}
+        }
+    }
+}
+
 pub fn evaluate(expression: &Expression, symbols: &mut Env) -> Result<Value, Error> {
     match expression {
         Expression::Primary(literal) => primary(literal, symbols),
-        Expression::Unary(operator, expression) => unary(operator, expression, symbols),
-        Expression::Binary(left, operator, right) => binary(left, operator, right, symbols),
-        Expression::Assignment(identifier, expression) => {
-            assignment(identifier, expression, symbols)
+        Expression::Unary(operator, expression, line) => {
+            unary(operator, expression, *line, symbols)
+        }
+        Expression::Binary(left, operator, right, line) => {
+            binary(left, operator, right, *line, symbols)
+        }
+        Expression::Assignment(identifier, expression, line) => {
+            assignment(identifier, expression, *line, symbols)
+        }
+        Expression::Conditional(condition, then, otherwise) => {
+            if truthy(evaluate(condition, symbols)?) {
+                evaluate(then, symbols)
+            } else {
+                evaluate(otherwise, symbols)
+            }
+        }
+        Expression::Lambda(params, body) => Ok(Value::Function(Function::new(
+            "lambda".to_string(),
+            params.clone(),
+            (**body).clone(),
+            symbols.clone(),
+        ))),
+        Expression::Call(callee, args, line) => {
+            let callee = evaluate(callee, symbols)?;
+            let args = arguments(args, symbols)?;
+            call(&callee, args, *line)
+        }
+        Expression::Interpolation(parts) => {
+            let mut string = String::new();
+            for part in parts {
+                string.push_str(&evaluate(part, symbols)?.to_string());
+            }
+            Ok(Value::String(LoxString::new(string)))
+        }
+        Expression::List(elements) => Ok(Value::List(List::new(arguments(elements, symbols)?))),
+        Expression::Map(entries, line) => {
+            let mut map = OrderedMap::default();
+            for (key, value) in entries {
+                let key = Key::new(&evaluate(key, symbols)?, *line)?;
+                map.insert(key, evaluate(value, symbols)?);
+            }
+            Ok(Value::Map(Map::new(map)))
+        }
+        Expression::Index(list, index, line) => {
+            let list = evaluate(list, symbols)?;
+            let index = evaluate(index, symbols)?;
+            get_index(&list, &index, *line)
+        }
+        Expression::Get(object, name, line) => match evaluate(object, symbols)? {
+            Value::Map(map) => {
+                let key = Key::String(LoxString::from(name.as_str()));
+                map.borrow().get(&key).cloned().ok_or_else(|| {
+                    Error::RuntimeError(*line, format!("Undefined property '{}'.", name))
+                })
+            }
+            _ => Err(Error::RuntimeError(
+                *line,
+                "Only maps and modules have properties.".to_string(),
+            )),
+        },
+        Expression::IndexAssignment(list, index, value, line) => {
+            let list = evaluate(list, symbols)?;
+            let index = evaluate(index, symbols)?;
+            let value = evaluate(value, symbols)?;
+            set_index(&list, &index, value, *line)
+        }
+        Expression::CompoundAssignment(target, operator, value, line) => {
+            let (_, updated) = update(target, *line, symbols, |current, symbols| {
+                let value = evaluate(value, symbols)?;
+                operate(current, operator, value, *line)
+            })?;
+            Ok(updated)
+        }
+        Expression::Increment(target, operator, fixity, line) => {
+            let (current, updated) = update(target, *line, symbols, |current, _| match current {
+                Value::Number(_) => operate(current, operator, Value::Number(1.0), *line),
+                _ => Err(Error::RuntimeError(
+                    *line,
+                    format!("Operand of '{}{}' must be a number.", operator, operator),
+                )),
+            })?;
+            match fixity {
+                Fixity::Prefix => Ok(updated),
+                Fixity::Postfix => Ok(current),
+            }
+        }
+    }
+}
+
+/// Reads a variable or element, works out its new value with `modify` and
+/// writes that back. The list or map and the index are only evaluated once.
+/// Returns the value from before the update and the one after it.
+fn update(
+    target: &Expression,
+    line: usize,
+    symbols: &mut Env,
+    modify: impl FnOnce(Value, &mut Env) -> Result<Value, Error>,
+) -> Result<(Value, Value), Error> {
+    match target {
+        Expression::Primary(Primary::Identifier(name, _)) => {
+            let current = variable(name, line, symbols)?;
+            let updated = modify(current.clone(), symbols)?;
+            symbols
+                .borrow_mut()
+                .assign(name, Symbol::variable(updated.clone()), line)?;
+            Ok((current, updated))
+        }
+        Expression::Index(list, index, _) => {
+            let list = evaluate(list, symbols)?;
+            let index = evaluate(index, symbols)?;
+            let current = get_index(&list, &index, line)?;
+            let updated = modify(current.clone(), symbols)?;
+            set_index(&list, &index, updated.clone(), line)?;
+            Ok((current, updated))
+        }
+        _ => Err(Error::RuntimeError(
+            line,
+            "Invalid assignment target".to_string(),
+        )),
+    }
+}
+
+/// The values a `for-in` loop visits: a list's elements, a map's keys in
+/// insertion order, a string's characters or a range's numbers. A list is
+/// read as the loop goes, so elements pushed inside the loop are visited too.
+///
+/// Anything else can be iterated through the iterator protocol. A map whose
+/// `iter` entry is a function is iterated by calling that function for an
+/// iterator. An iterator is a `next` function, or a map with one, which
+/// returns one value per call and nil once there are no more.
+pub fn iterate(
+    value: &Value,
+    line: usize,
+) -> Result<Box<dyn Iterator<Item = Result<Value, Error>>>, Error> {
+    let values: Box<dyn Iterator<Item = Value>> = match value {
+        Value::List(list) => {
+            let list = list.clone();
+            Box::new((0..).map_while(move |i| list.borrow().get(i).cloned()))
+        }
+        Value::Map(map) => {
+            if let Some(iter @ Value::Function(_)) = property(map, "iter") {
+                return iterator(call(&iter, Vec::new(), line)?, line);
+            }
+            let keys: Vec<Value> = map.borrow().iter().map(|(key, _)| key.to_value()).collect();
+            Box::new(keys.into_iter())
+        }
+        Value::String(string) => {
+            let chars: Vec<Value> = string
+                .as_str()
+                .chars()
+                .map(|c| Value::String(LoxString::from(c.to_string())))
+                .collect();
+            Box::new(chars.into_iter())
+        }
+        Value::Range(range) => Box::new(range.iter().map(Value::Number)),
+        Value::Function(_) => return iterator(value.clone(), line),
+        _ => {
+            return Err(Error::RuntimeError(
+                line,
+                format!("Can't iterate over {}.", value),
+            ));
         }
+    };
+    Ok(Box::new(values.map(Ok)))
+}
+
+// Calls an iterator's `next` function until it returns nil. The loop stops
+// at the first error, since the caller gives up on it.
+fn iterator(
+    iterator: Value,
+    line: usize,
+) -> Result<Box<dyn Iterator<Item = Result<Value, Error>>>, Error> {
+    let next = match &iterator {
+        Value::Function(_) => iterator,
+        Value::Map(map) => match property(map, "next") {
+            Some(next @ Value::Function(_)) => next,
+            _ => {
+                return Err(Error::RuntimeError(
+                    line,
+                    "An iterator map needs a 'next' function.".to_string(),
+                ));
+            }
+        },
+        _ => {
+            return Err(Error::RuntimeError(
+                line,
+                format!("Expected an iterator from 'iter', got {}.", repr(&iterator)),
+            ));
+        }
+    };
+
+    Ok(Box::new(std::iter::from_fn(move || {
+        match call(&next, Vec::new(), line) {
+            Ok(Value::Nil) => None,
+            result => Some(result),
+        }
+    })))
+}
+
+fn property(map: &Map, name: &str) -> Option<Value> {
+    map.borrow()
+        .get(&Key::String(LoxString::intern(name)))
+        .cloned()
+}
+
+fn get_index(list: &Value, index: &Value, line: usize) -> Result<Value, Error> {
+    match list {
+        Value::List(list) => {
+            let values = list.borrow();
+            let index = List::position(index, values.len(), line)?;
+            Ok(values[index].clone())
+        }
+        Value::Map(map) => {
+            let key = Key::new(index, line)?;
+            match map.borrow().get(&key) {
+                Some(value) => Ok(value.clone()),
+                None => Err(Error::RuntimeError(
+                    line,
+                    format!("Undefined key {}.", repr(index)),
+                )),
+            }
+        }
+        Value::String(string) => string::char_at(string, index, line),
+        _ => Err(Error::RuntimeError(
+            line,
+            "Only strings, lists and maps can be indexed.".to_string(),
+        )),
+    }
+}
+
+fn set_index(list: &Value, index: &Value, value: Value, line: usize) -> Result<Value, Error> {
+    match list {
+        Value::List(list) => {
+            let mut values = list.borrow_mut();
+            let index = List::position(index, values.len(), line)?;
+            values[index] = value.clone();
+            Ok(value)
+        }
+        Value::Map(map) => {
+            let key = Key::new(index, line)?;
+            let mut map = map.borrow_mut();
+            map.check_writable(&key, line)?;
+            map.insert(key, value.clone());
+            Ok(value)
+        }
+        Value::String(_) => Err(Error::RuntimeError(
+            line,
+            "Strings are immutable.".to_string(),
+        )),
+        _ => Err(Error::RuntimeError(
+            line,
+            "Only lists and maps can be indexed.".to_string(),
+        )),
     }
 }
 
 fn assignment(
     identifier: &Primary,
     expression: &Expression,
+    line: usize,
     environment: &mut Env,
 ) -> Result<Value, Error> {
     let value = evaluate(expression, environment)?;
 
     let name = match identifier {
-        Primary::Identifier(name) => name,
+        Primary::Identifier(name, _) => name,
         _ => {
             return Err(Error::RuntimeError(
-                1,
+                line,
                 "Invalid assignment target".to_string(),
             ));
         }
@@ -58,7 +329,7 @@ fn assignment(
 
     environment
         .borrow_mut()
-        .assign(name, Symbol::Variable(value.clone()))?;
+        .assign(name, Symbol::variable(value.clone()), line)?;
 
     Ok(value)
 }
@@ -66,100 +337,175 @@ fn assignment(
 fn primary(primary: &Primary, symbols: &mut Env) -> Result<Value, Error> {
     match primary {
         Primary::Number(number) => Ok(Value::Number(number.to_owned())),
-        Primary::String(string) => Ok(Value::String(string.to_string())),
+        Primary::String(string) => Ok(Value::String(LoxString::intern(string))),
         Primary::True => Ok(Value::Boolean(true)),
         Primary::False => Ok(Value::Boolean(false)),
         Primary::Nil => Ok(Value::Nil),
         Primary::Grouping(expression) => evaluate(expression, symbols),
-        Primary::Identifier(identifier) => variable(identifier, symbols),
-        Primary::Function(name, arguments) => function(name, arguments.to_vec(), symbols),
+        Primary::Identifier(identifier, line) => variable(identifier, *line, symbols),
+        Primary::Function(name, arguments, line) => function(name, arguments, *line, symbols),
     }
 }
 
-fn function(name: &str, args: Vec<Expression>, symbols: &Env) -> Result<Value, Error> {
+fn function(
+    name: &str,
+    args: &[Expression],
+    line: usize,
+    symbols: &mut Env,
+) -> Result<Value, Error> {
     let symbol = symbols.borrow().get(name);
 
     if let Some(symbol) = symbol {
         match symbol {
-            Symbol::Variable(_) => {
-                return Err(Error::RuntimeError(1, "Variable not callable".to_string()));
+            Symbol::Variable(slot) | Symbol::Constant(slot) => {
+                let callee = unpack(&slot);
+                let args = arguments(args, symbols)?;
+                return call(&callee, args, line);
             }
-            Symbol::Function(params, body) => {
-                return call_function(params, args, body, symbols.to_owned());
+            Symbol::Function(function) => {
+                let args = arguments(args, symbols)?;
+                return call_function(function, args, line);
             }
         }
     }
 
-    match name {
-        "clock" => clock(),
-        _ => Err(Error::RuntimeError(1, "Unknown method".to_string())),
+    match lookup(name) {
+        Some(native) => call_native(native, &arguments(args, symbols)?, line),
+        None => Err(Error::RuntimeError(line, "Unknown method".to_string())),
     }
 }
 
-fn call_function(
-    params: Vec<Token>,
-    args: Vec<Expression>,
-    body: Statement,
-    closure_env: Env,
-) -> Result<Value, Error> {
-    let mut function_env = Environment::with_enclosing(closure_env);
+/// Runs the expression of a `return`, except that a call to a user function
+/// in tail position isn't entered but handed back as a tail call, so the
+/// caller can reuse its frame. That's any call, whether by name, of a lambda,
+/// map member or call result, including one in a branch of `?:` or in
+/// parentheses.
+pub fn tail_call(expression: &Expression, symbols: &mut Env) -> Result<ControlFlow, Error> {
+    let (function, args, line) = match expression {
+        Expression::Primary(Primary::Function(name, args, line)) => {
+            let symbol = symbols.borrow().get(name);
+            let function = match symbol {
+                Some(Symbol::Function(function)) => function,
+                Some(Symbol::Variable(slot) | Symbol::Constant(slot)) => match unpack(&slot) {
+                    Value::Function(function) => function,
+                    _ => return Ok(ControlFlow::Return(evaluate(expression, symbols)?)),
+                },
+                None => return Ok(ControlFlow::Return(evaluate(expression, symbols)?)),
+            };
+            (function, arguments(args, symbols)?, *line)
+        }
+        Expression::Call(callee, args, line) => {
+            let callee = evaluate(callee, symbols)?;
+            let args = arguments(args, symbols)?;
+            match callee {
+                Value::Function(function) => (function, args, *line),
+                callee => return Ok(ControlFlow::Return(call(&callee, args, *line)?)),
+            }
+        }
+        Expression::Conditional(condition, then, otherwise) => {
+            let branch = if truthy(evaluate(condition, symbols)?) {
+                then
+            } else {
+                otherwise
+            };
+            return tail_call(branch, symbols);
+        }
+        Expression::Primary(Primary::Grouping(inner)) => return tail_call(inner, symbols),
+        _ => return Ok(ControlFlow::Return(evaluate(expression, symbols)?)),
+    };
 
-    let mut arg_queue: VecDeque<Expression> = args.into();
-    for param in params {
-        let arg_expr = arg_queue.pop_front().unwrap();
-        let arg_value = evaluate(&arg_expr, &mut function_env)?;
-        function_env
-            .borrow_mut()
-            .define(param.get_identifier(), Symbol::Variable(arg_value));
-    }
+    Ok(ControlFlow::TailCall(TailCall {
+        function,
+        args,
+        line,
+    }))
+}
 
-    match evaluate_statement(body, &mut function_env) {
-        Ok(()) => Ok(Value::Nil),
-        Err(ControlFlow::Return(v)) => Ok(v),
-        Err(ControlFlow::Runtime(e)) => Err(e),
+/// Calls a function value, such as a lambda held in a variable or passed to a
+/// native as a callback.
+pub fn call(callee: &Value, args: Vec<Value>, line: usize) -> Result<Value, Error> {
+    match callee {
+        Value::Function(function) => call_function(function.clone(), args, line),
+        _ => Err(Error::RuntimeError(
+            line,
+            format!("Can only call functions, not {}.", repr(callee)),
+        )),
     }
 }
 
-fn clock() -> Result<Value, Error> {
-    let now = SystemTime::now();
-    if let Ok(now) = now.duration_since(SystemTime::UNIX_EPOCH) {
-        Ok(Value::Number(now.as_secs_f64()))
-    } else {
-        Err(Error::RuntimeError(
-            1,
-            "Failed to get current time".to_string(),
-        ))
+fn arguments(args: &[Expression], symbols: &mut Env) -> Result<Vec<Value>, Error> {
+    args.iter().map(|arg| evaluate(arg, symbols)).collect()
+}
+
+pub fn call_function(
+    mut function: Rc<Function>,
+    mut args: Vec<Value>,
+    mut line: usize,
+) -> Result<Value, Error> {
+    loop {
+        if function.params.len() != args.len() {
+            let error = format!(
+                "Expected {} arguments but got {}.",
+                function.params.len(),
+                args.len()
+            );
+            return Err(Error::RuntimeError(line, error));
+        }
+
+        let mut function_env = Environment::with_enclosing(function.closure.clone());
+
+        for (param, arg_value) in function.params.iter().zip(args) {
+            function_env
+                .borrow_mut()
+                .define(param.get_identifier(), Symbol::variable(arg_value));
+        }
+
+        // A tail call replaces the current frame instead of nesting another
+        // `call_function`, so tail recursion runs in constant stack.
+        match evaluate_statement(function.body.clone(), &mut function_env) {
+            Ok(()) => return Ok(Value::Nil),
+            Err(ControlFlow::Return(v)) => return Ok(v),
+            Err(ControlFlow::TailCall(call)) => {
+                function = call.function;
+                args = call.args;
+                line = call.line;
+            }
+            Err(ControlFlow::Runtime(e)) => return Err(e),
+        }
     }
 }
 
-fn variable(string: &str, symbols: &Env) -> Result<Value, Error> {
+fn variable(string: &str, line: usize, symbols: &Env) -> Result<Value, Error> {
     match symbols.borrow().get(string) {
         Some(value) => match value {
-            Symbol::Variable(val) => Ok(val),
-            Symbol::Function(_, _) => {
-                let val = format!("<fn {}>", string);
-                Ok(Value::String(val))
-            }
+            Symbol::Variable(slot) | Symbol::Constant(slot) => Ok(unpack(&slot)),
+            Symbol::Function(function) => Ok(Value::Function(function)),
         },
-        None => Err(Error::RuntimeError(1, "Unknown identifier".to_string())),
+        None => constant(string)
+            .ok_or_else(|| Error::RuntimeError(line, "Unknown identifier".to_string())),
     }
 }
 
-fn unary(unary: &Unary, expression: &Expression, symbols: &mut Env) -> Result<Value, Error> {
+fn unary(
+    unary: &Unary,
+    expression: &Expression,
+    line: usize,
+    symbols: &mut Env,
+) -> Result<Value, Error> {
     let expression = evaluate(expression, symbols)?;
 
     match unary {
-        Unary::Minus => minus(expression),
+        Unary::Minus => minus(expression, line),
         Unary::Bang => check_bang(expression),
     }
 }
 
-fn minus(statement: Value) -> Result<Value, Error> {
+fn minus(statement: Value, line: usize) -> Result<Value, Error> {
     match statement {
         Value::Number(number) => Ok(Value::Number(-number)),
         _ => {
             let error = "Operand must be a number.".to_string();
-            Err(Error::RuntimeError(1, error))
+            Err(Error::RuntimeError(line, error))
         }
     }
 }
@@ -179,6 +525,7 @@ fn binary(
     left: &Expression,
     operator: &Operator,
     right: &Expression,
+    line: usize,
     symbols: &mut Env,
 ) -> Result<Value, Error> {
     let left = evaluate(left, symbols)?;
@@ -206,30 +553,57 @@ fn binary(
         }
     }
 
+    if matches!(operator, Operator::Coalesce) {
+        if matches!(left, Value::Nil) {
+            return evaluate(right, symbols);
+        }
+        return Ok(left);
+    }
+
     let right = evaluate(right, symbols)?;
+    operate(left, operator, right, line)
+}
 
+fn operate(left: Value, operator: &Operator, right: Value, line: usize) -> Result<Value, Error> {
     match operator {
-        Operator::Plus => plus(&left, &right),
+        Operator::Plus => plus(&left, &right, line),
         Operator::BangEqual => Ok(Value::Boolean(left != right)),
         Operator::EqualEqual => Ok(Value::Boolean(equal(&left, &right))),
         _ => {
             if let (Value::Number(left), Value::Number(right)) = (left, right) {
-                return arithmetic(left, operator, right);
+                return arithmetic(left, operator, right, line);
             }
             let error = format!(
                 "Unable to execute operator {} on strings ors booleans",
                 operator
             );
-            Err(Error::RuntimeError(1, error))
+            Err(Error::RuntimeError(line, error))
         }
     }
 }
 
-fn arithmetic(left: f64, operator: &Operator, right: f64) -> Result<Value, Error> {
+fn arithmetic(left: f64, operator: &Operator, right: f64, line: usize) -> Result<Value, Error> {
     match operator {
         Operator::Minus => Ok(Value::Number(left - right)),
         Operator::Star => Ok(Value::Number(left * right)),
+        // Plain division follows IEEE 754, so dividing by zero gives an
+        // infinity or NaN. The whole-number operators have no such value to
+        // give back, so they fail instead.
         Operator::Division => Ok(Value::Number(left / right)),
+        Operator::Modulo | Operator::IntegerDivision if right == 0.0 => Err(Error::RuntimeError(
+            line,
+            format!("Division by zero in '{}'.", operator),
+        )),
+        Operator::Modulo => {
+            let remainder = left % right;
+            if remainder != 0.0 && (remainder < 0.0) != (right < 0.0) {
+                Ok(Value::Number(remainder + right))
+            } else {
+                Ok(Value::Number(remainder))
+            }
+        }
+        Operator::IntegerDivision => Ok(Value::Number((left / right).floor())),
+        Operator::Power => Ok(Value::Number(left.powf(right))),
         Operator::Less => Ok(Value::Boolean(left < right)),
         Operator::LessEqual => Ok(Value::Boolean(left <= right)),
         Operator::Greater => Ok(Value::Boolean(left > right)),
@@ -240,24 +614,28 @@ fn arithmetic(left: f64, operator: &Operator, right: f64) -> Result<Value, Error
     }
 }
 
-fn plus(left: &Value, right: &Value) -> Result<Value, Error> {
+fn plus(left: &Value, right: &Value, line: usize) -> Result<Value, Error> {
     match (left, right) {
-        (Value::String(left), Value::String(right)) => {
-            Ok(Value::String(format!("{}{}", left, right)))
-        }
+        (Value::String(left), Value::String(right)) => Ok(Value::String(left.concat(right))),
         (Value::Number(left), Value::Number(right)) => Ok(Value::Number(left + right)),
         _ => Err(Error::RuntimeError(
-            1,
+            line,
             "Opperands must be 2 numbers or 2 strings".to_string(),
         )),
     }
 }
 
-fn equal(left: &Value, right: &Value) -> bool {
+pub fn equal(left: &Value, right: &Value) -> bool {
     match (left, right) {
         (Value::String(string1), Value::String(string2)) => string1 == string2,
         (Value::Number(number1), Value::Number(number2)) => number1 == number2,
         (Value::Boolean(bool1), Value::Boolean(bool2)) => bool1 == bool2,
+        (Value::List(list1), Value::List(list2)) => list1 == list2,
+        (Value::Map(map1), Value::Map(map2)) => map1 == map2,
+        (Value::Range(range1), Value::Range(range2)) => range1 == range2,
+        (Value::Function(function1), Value::Function(function2)) => {
+            Rc::ptr_eq(function1, function2)
+        }
         _ => false,
     }
 }
@@ -265,8 +643,406 @@ fn equal(left: &Value, right: &Value) -> bool {
 pub fn truthy(value: Value) -> bool {
     match value {
         Value::String(_) => true,
+        Value::List(_) | Value::Map(_) | Value::Range(_) | Value::Function(_) => true,
         Value::Boolean(bool) => bool,
         Value::Number(number) => number != 0.0,
         Value::Nil => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::enums::environment::{Environment, Symbol, unpack};
+    use crate::enums::error::Error;
+    use crate::enums::statement::Statement;
+    use crate::enums::token::Token;
+    use crate::parser::parse_source;
+    use crate::run::{ControlFlow, evaluate_statements};
+    use crate::tokenizer::scan;
+
+    // Runs `source` and returns how its `result` variable prints.
+    fn result(source: &str) -> String {
+        let (statements, errors) = parse_source(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let mut environment = Environment::new();
+        if let Err(error) = evaluate_statements(statements, &mut environment) {
+            panic!("{:?}", error);
+        }
+        match environment.borrow().get("result") {
+            Some(Symbol::Variable(slot) | Symbol::Constant(slot)) => unpack(&slot).to_string(),
+            other => panic!("result isn't a variable: {:?}", other),
+        }
+    }
+
+    // The line and message of the runtime error `source` fails with.
+    fn runtime_error(source: &str) -> (usize, String) {
+        let (statements, errors) = parse_source(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        match evaluate_statements(statements, &mut Environment::new()) {
+            Err(ControlFlow::Runtime(Error::RuntimeError(line, message))) => (line, message),
+            other => panic!("expected a runtime error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn division_by_zero_reports_the_operator_line() {
+        assert_eq!(
+            runtime_error("var a = 5;\nvar b = 0;\nprint a\n  % b;"),
+            (4, "Division by zero in '%'.".to_string())
+        );
+        assert_eq!(runtime_error("\n\nprint 1 ~/ 0;").0, 3);
+    }
+
+    #[test]
+    fn operand_errors_report_the_operator_line() {
+        assert_eq!(runtime_error("\nprint \"x\" + 1;").0, 2);
+        assert_eq!(runtime_error("\n\nprint true * 2;").0, 3);
+        assert_eq!(runtime_error("var s = \"a\";\n\ns -= 1;").0, 3);
+    }
+
+    #[test]
+    fn name_unary_and_arity_errors_report_their_line() {
+        assert_eq!(
+            runtime_error("\n\nprint nope;"),
+            (3, "Unknown identifier".to_string())
+        );
+        assert_eq!(runtime_error("\nprint -\"s\";").0, 2);
+        assert_eq!(
+            runtime_error("fun two(a, b) {}\n\ntwo(1);"),
+            (3, "Expected 2 arguments but got 1.".to_string())
+        );
+        // A tail call's arity is checked against the line of the call.
+        assert_eq!(
+            runtime_error("fun two(a, b) {}\nfun tail() {\n  return two(1);\n}\ntail();").0,
+            3
+        );
+    }
+
+    #[test]
+    fn lists_are_built_indexed_and_changed_in_place() {
+        assert_eq!(
+            result(r#"var result = [1, "two", [3], nil];"#),
+            r#"[1, "two", [3], nil]"#
+        );
+        assert_eq!(result("var l = [1, 2, 3]; var result = l[0] + l[2];"), "4");
+        assert_eq!(
+            result("var l = [1, 2]; var alias = l; alias[0] = 9; push(l, 3); var result = alias;"),
+            "[9, 2, 3]"
+        );
+        assert_eq!(
+            result("var l = [1, 2, 3]; insert(l, 3, 4); remove(l, 0); pop(l); var result = l;"),
+            "[2, 3]"
+        );
+        assert_eq!(
+            result("var result = filter(map([1, 2, 3, 4], (x) => x * x), (x) => x > 4);"),
+            "[9, 16]"
+        );
+        // Lists are equal only if they're the same list.
+        assert_eq!(
+            result("var l = [1]; var result = [l == l, [1] == [1]];"),
+            "[true, false]"
+        );
+        assert_eq!(
+            result("var l = [1]; push(l, l); var result = l;"),
+            "[1, [...]]"
+        );
+    }
+
+    #[test]
+    fn list_indexes_must_be_in_bounds_integers() {
+        assert_eq!(
+            runtime_error("var l = [1, 2];\nprint l[2];"),
+            (2, "Index 2 out of bounds for length 2.".to_string())
+        );
+        assert_eq!(
+            runtime_error("var l = [1, 2];\nl[-1] = 0;"),
+            (2, "Index -1 out of bounds for length 2.".to_string())
+        );
+        assert_eq!(
+            runtime_error("print [1][0.5];").1,
+            "Index must be an integer, got 0.5."
+        );
+        assert_eq!(runtime_error("pop([]);").1, "Can't pop from an empty list.");
+    }
+
+    #[test]
+    fn maps_keep_insertion_order_and_fold_equal_keys() {
+        assert_eq!(
+            result(r#"var m = {"b": 1, "a": 2}; m["c"] = 3; m["b"] = 4; var result = m;"#),
+            r#"{"b": 4, "a": 2, "c": 3}"#
+        );
+        // -0 and 0 are the same key; so are strings built different ways.
+        assert_eq!(
+            result(
+                r#"var m = {0: "zero"}; m[-0] = "again"; m["a" + "b"] = 1; var result = [len(m), m["ab"], m[0]];"#
+            ),
+            r#"[2, 1, "again"]"#
+        );
+        assert_eq!(
+            result(r#"var m = {"k": 1, nil: 2, true: 3}; var result = [keys(m), values(m), m.k];"#),
+            r#"[["k", nil, true], [1, 2, 3], 1]"#
+        );
+        assert_eq!(
+            result(
+                r#"var m = {"k": 1}; var gone = delete(m, "k"); var result = [gone, has(m, "k"), delete(m, "k")];"#
+            ),
+            "[1, false, nil]"
+        );
+    }
+
+    #[test]
+    fn map_keys_must_be_plain_values() {
+        assert_eq!(
+            runtime_error("var m = {};\nm[[1]] = 2;"),
+            (
+                2,
+                "Map keys must be strings, numbers, booleans or nil.".to_string()
+            )
+        );
+        assert_eq!(runtime_error("print {}[0 / 0];").1, "Map key can't be NaN.");
+        assert_eq!(
+            runtime_error("var m = {};\nprint m.missing;"),
+            (2, "Undefined property 'missing'.".to_string())
+        );
+        assert_eq!(
+            runtime_error("var n = 1;\nprint n.x;").1,
+            "Only maps and modules have properties."
+        );
+    }
+
+    #[test]
+    fn escapes_turn_into_their_characters() {
+        let source = r#"var result = "tab\tquote\" slash\\ dollar\${x} \u{48}\u{1F600}";"#;
+        assert_eq!(result(source), "tab\tquote\" slash\\ dollar${x} H\u{1F600}");
+        assert_eq!(result(r#"var result = len("a\nb\0");"#), "4");
+
+        let (_, errors) = scan(r#"print "\q";"#, 1);
+        assert_eq!(
+            errors.iter().map(Token::to_string).collect::<Vec<_>>(),
+            ["[line 1] Error: Unknown escape sequence '\\q'."]
+        );
+    }
+
+    #[test]
+    fn lines_are_counted_through_multi_line_strings() {
+        assert_eq!(
+            runtime_error("var s = \"one\ntwo\nthree\";\nprint nope;"),
+            (4, "Unknown identifier".to_string())
+        );
+        assert_eq!(result("var result = \"a\nb\";"), "a\nb");
+    }
+
+    #[test]
+    fn interpolation_prints_each_value_in_place() {
+        assert_eq!(
+            result(r#"var n = 2; var result = "${n} + ${n} = ${n + n}, ${[n, "s"]} ${nil}";"#),
+            r#"2 + 2 = 4, [2, "s"] nil"#
+        );
+        assert_eq!(
+            result(r#"var who = "x"; var result = "outer ${"inner ${who + "}"}"}!";"#),
+            "outer inner x}!"
+        );
+        assert_eq!(
+            result("var m = {\"k\": 1}; var result = \"${ m[\"k\"] }\n${len(\"ab\")}\";"),
+            "1\n2"
+        );
+    }
+
+    #[test]
+    fn interpolations_need_an_expression() {
+        for source in [
+            r#"print "${}";"#,
+            r#"print "${1 +}";"#,
+            r#"print "${1 2}";"#,
+        ] {
+            let (_, errors) = parse_source(source);
+            assert!(
+                matches!(&errors[..], [Error::ParseError(1, _, Some(_))]),
+                "{}: {:?}",
+                source,
+                errors
+            );
+        }
+    }
+
+    #[test]
+    fn math_natives_and_constants() {
+        assert_eq!(
+            result("var result = [sqrt(16), pow(2, 10), abs(-3), floor(-1.5), ceil(1.2)];"),
+            "[4, 1024, 3, -2, 2]"
+        );
+        assert_eq!(
+            result("var result = [round(2.5), round(-2.5), min(3, 1, 2), max(3, 1, 2)];"),
+            "[3, -3, 1, 3]"
+        );
+        assert_eq!(
+            result(
+                "var result = [floor(PI * 100), round(log(E)), exp(0), is_nan(0 / 0), is_finite(1 / 0)];"
+            ),
+            "[314, 1, 1, true, false]"
+        );
+        assert_eq!(
+            result(
+                r#"var result = [parse_number(" -1.5 "), parse_number("1."), parse_number("x"), to_string([1])];"#
+            ),
+            r#"[-1.5, nil, nil, "[1]"]"#
+        );
+        // A program's own names shadow natives and constants.
+        assert_eq!(
+            result("fun abs(x) { return \"mine\"; } var PI = 3; var result = [abs(-1), PI];"),
+            r#"["mine", 3]"#
+        );
+    }
+
+    #[test]
+    fn math_natives_take_numbers() {
+        assert_eq!(
+            runtime_error("\nprint sqrt(\"4\");"),
+            (2, "Arguments to 'sqrt' must be numbers, got 4.".to_string())
+        );
+        assert_eq!(
+            runtime_error("print pow(2);").1,
+            "Expected 2 arguments but got 1."
+        );
+    }
+
+    #[test]
+    fn compound_assignment_and_increments_update_in_place() {
+        assert_eq!(
+            result("var a = 10; a += 5; a -= 3; a *= 2; a /= 4; a %= 4; var result = a;"),
+            "2"
+        );
+        assert_eq!(result(r#"var s = "ab"; s += "c"; var result = s;"#), "abc");
+        assert_eq!(
+            result("var i = 1; var result = [i++, i, ++i, i--, --i, i];"),
+            "[1, 2, 3, 3, 1, 1]"
+        );
+        // Index targets are evaluated once.
+        assert_eq!(
+            result(
+                "var l = [1, 2]; var n = 0; fun at() { n++; return 1; } l[at()] += 10; l[at()]++; var result = [l, n];"
+            ),
+            "[[1, 13], 2]"
+        );
+        assert_eq!(
+            result(r#"var m = {"k": 1}; m["k"] *= 3; var result = m;"#),
+            r#"{"k": 3}"#
+        );
+    }
+
+    #[test]
+    fn only_names_and_indexes_can_be_updated() {
+        for source in ["1 += 2;", "(a)++;", "++f();", "var a; a + 1 = 2;"] {
+            let (_, errors) = parse_source(source);
+            assert!(
+                matches!(&errors[..], [Error::ParseError(_, message, Some(_))] if message == "Invalid assignment"),
+                "{}: {:?}",
+                source,
+                errors
+            );
+        }
+        assert_eq!(
+            runtime_error("var s = \"x\";\ns++;"),
+            (2, "Operand of '++' must be a number.".to_string())
+        );
+        assert_eq!(runtime_error("nope += 1;").1, "Unknown identifier");
+    }
+
+    #[test]
+    fn conditionals_and_coalescing_evaluate_only_what_they_need() {
+        assert_eq!(
+            result("var result = [true ? 1 : 2, nil ? 1 : 2, \"\" ? \"s\" : 2, 0 ? 1 : \"zero\"];"),
+            r#"[1, 2, "s", "zero"]"#
+        );
+        // `?:` nests to the right.
+        assert_eq!(
+            result("var n = 5; var result = n < 0 ? \"neg\" : n == 0 ? \"zero\" : \"pos\";"),
+            "pos"
+        );
+        // `??` only replaces nil, so false and 0, though falsy, are kept.
+        assert_eq!(
+            result("var result = [nil ?? 1, false ?? 1, 0 ?? 1, nil ?? nil ?? 3];"),
+            "[1, false, 0, 3]"
+        );
+        assert_eq!(
+            result(
+                "var calls = 0; fun hit() { calls++; return 1; } var a = 1 ?? hit(); var b = true ? 2 : hit(); var result = calls;"
+            ),
+            "0"
+        );
+    }
+
+    #[test]
+    fn lambdas_capture_their_scope() {
+        assert_eq!(
+            result(
+                "fun counter() { var n = 0; return () => ++n; } var c = counter(); c(); var d = counter(); var result = [c(), d()];"
+            ),
+            "[2, 1]"
+        );
+        assert_eq!(
+            result(
+                "var add = fun (a, b) { return a + b; }; var result = [add(1, 2), ((x) => x * 2)(4)];"
+            ),
+            "[3, 8]"
+        );
+        // Each arrow is its own function, and a body with no return gives nil.
+        assert_eq!(
+            result(
+                "var f = () => 1; var g = () => 1; var h = fun () {}; var result = [f == f, f == g, h()];"
+            ),
+            "[true, false, nil]"
+        );
+        assert_eq!(
+            result(
+                "var adders = map([1, 2], (n) => (x) => x + n); var result = [adders[0](10), adders[1](10)];"
+            ),
+            "[11, 12]"
+        );
+    }
+
+    #[test]
+    fn lambdas_check_their_arity_and_callees_must_be_functions() {
+        assert_eq!(
+            runtime_error("var f = (a) => a;\nf(1, 2);"),
+            (2, "Expected 1 arguments but got 2.".to_string())
+        );
+        assert_eq!(
+            runtime_error("var x = 1;\nx();").1,
+            "Can only call functions, not 1."
+        );
+    }
+
+    #[test]
+    fn block_comments_nest_and_keep_lines_counted() {
+        assert_eq!(
+            result("var result = 1 /* a /* nested */ still comment */ + 2; // 3"),
+            "3"
+        );
+        assert_eq!(
+            runtime_error("/* one\ntwo /* three\n*/ */\nprint nope;"),
+            (4, "Unknown identifier".to_string())
+        );
+
+        let (_, errors) = scan("print 1;\n/* open /* inner */\n", 1);
+        assert_eq!(
+            errors.iter().map(Token::to_string).collect::<Vec<_>>(),
+            ["[line 2] Error: Unterminated block comment."]
+        );
+    }
+
+    #[test]
+    fn doc_comments_attach_to_the_declaration_after_them() {
+        let source = "/// Adds.\n///\n/// Twice over.\n//// not a doc comment\nfun add(a) { return a; }\n// plain\nvar x = 1;";
+        let (statements, errors) = parse_source(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let docs: Vec<Option<String>> = statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::Fn(_, _, _, doc) | Statement::Declaration(_, _, doc) => doc.clone(),
+                _ => panic!("unexpected statement"),
+            })
+            .collect();
+        assert_eq!(docs, [Some("Adds.\n\nTwice over.".to_string()), None]);
+    }
+}
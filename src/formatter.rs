@@ -0,0 +1,470 @@
+use std::fs;
+use std::process;
+
+use crate::cst::{self, NodeKind, SyntaxElement, SyntaxNode, SyntaxToken, TriviaKind};
+use crate::enums::token::Token;
+use crate::parser::parse_source;
+use crate::tokenizer::scan;
+use crate::utils::get_file_contents;
+
+/// Runs `fmt <file>`. By default the formatted program is printed;
+/// `--check` only reports whether the file would change, and `--write`
+/// rewrites it in place.
+pub fn fmt(filename: &str, flags: &[String]) {
+    let source = get_file_contents(filename);
+    let formatted = match format_source(&source) {
+        Ok(formatted) => formatted,
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{}", error);
+            }
+            process::exit(65);
+        }
+    };
+
+    if flags.iter().any(|flag| flag == "--check") {
+        if formatted != source {
+            eprintln!("{} is not formatted", filename);
+            process::exit(1);
+        }
+    } else if flags.iter().any(|flag| flag == "--write") {
+        if formatted != source && fs::write(filename, &formatted).is_err() {
+            eprintln!("Failed to write file {}", filename);
+            process::exit(74);
+        }
+    } else {
+        print!("{}", formatted);
+    }
+}
+
+/// Formats a whole program. Programs that don't scan or parse are refused,
+/// since there's no telling what their broken parts mean.
+pub fn format_source(source: &str) -> Result<String, Vec<String>> {
+    let (_, errors) = scan(source, 1);
+    if !errors.is_empty() {
+        return Err(errors.iter().map(Token::to_string).collect());
+    }
+    let (_, errors) = parse_source(source);
+    if !errors.is_empty() {
+        return Err(errors.iter().map(|error| error.to_string()).collect());
+    }
+
+    let formatted = layout(source);
+
+    // Only layout may change: the formatted program has to scan to the same
+    // tokens, with the same doc comments, as the original.
+    if fingerprint(&formatted) != fingerprint(source) {
+        return Err(vec![
+            "Formatting would change the program, so it was left alone.".to_string(),
+        ]);
+    }
+    Ok(formatted)
+}
+
+fn layout(source: &str) -> String {
+    let mut printer = Printer::default();
+    printer.root(&cst::parse(source));
+    printer.finish()
+}
+
+fn fingerprint(source: &str) -> Vec<(String, Option<String>)> {
+    cst::lossless_tokens(source)
+        .into_iter()
+        .map(|token| (token.text, token.lexeme.doc))
+        .collect()
+}
+
+const INDENT: &str = "  ";
+
+// How a token is spaced, beyond what its kind says.
+#[derive(Clone, Copy, PartialEq)]
+enum Role {
+    Plain,
+    MapOpen,
+    MapClose,
+    MapColon,
+}
+
+struct Written {
+    token: Token,
+    text: String,
+    role: Role,
+    unary: bool,
+    ends_operand: bool,
+}
+
+// A line break owed before the next thing written.
+struct Break {
+    indent: usize,
+    blank: bool,
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    pending: Option<Break>,
+    previous: Option<Written>,
+}
+
+impl Printer {
+    fn finish(mut self) -> String {
+        let trimmed = self.out.trim_end().len();
+        self.out.truncate(trimmed);
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.out
+    }
+
+    fn root(&mut self, root: &SyntaxNode) {
+        for child in &root.children {
+            match child {
+                SyntaxElement::Node(node) => self.statement(node, 0),
+                // Only the end of the file is left over, with any comments
+                // after the last statement.
+                SyntaxElement::Token(token) => self.leading(token, Some(0), 1),
+            }
+        }
+    }
+
+    // Each statement starts its own line. `else`, `catch` and `finally` go on
+    // the line of the `}` before them, or start a line after a `;`.
+    fn statement(&mut self, statement: &SyntaxNode, depth: usize) {
+        let mut line = Some(depth);
+
+        for child in &statement.children {
+            match child {
+                SyntaxElement::Node(node) => {
+                    self.node(node, depth, line);
+                    line = None;
+                }
+                SyntaxElement::Token(token) => {
+                    self.token(token, Role::Plain, line, depth + 1);
+                    let semicolon = token.lexeme.token == Token::SemiColon;
+                    line = semicolon.then_some(depth);
+                }
+            }
+        }
+    }
+
+    fn node(&mut self, node: &SyntaxNode, depth: usize, line: Option<usize>) {
+        match node.kind {
+            NodeKind::Block => self.block(node, depth, line),
+            NodeKind::Root | NodeKind::Statement => self.statement(node, depth),
+            NodeKind::Group | NodeKind::Brackets | NodeKind::Map => {
+                let map = node.kind == NodeKind::Map;
+                let last = node.children.len() - 1;
+                // Colons in a map separate keys from values, unless a `?`
+                // in the same entry is still waiting for its `:`.
+                let mut questions = 0;
+
+                for (i, child) in node.children.iter().enumerate() {
+                    match child {
+                        SyntaxElement::Node(inner) => self.node(inner, depth, None),
+                        SyntaxElement::Token(token) => {
+                            let role = match &token.lexeme.token {
+                                _ if !map => Role::Plain,
+                                Token::LeftBrace if i == 0 => Role::MapOpen,
+                                Token::RightBrace if i == last => Role::MapClose,
+                                Token::Question => {
+                                    questions += 1;
+                                    Role::Plain
+                                }
+                                Token::Comma => {
+                                    questions = 0;
+                                    Role::Plain
+                                }
+                                Token::Colon if questions == 0 => Role::MapColon,
+                                Token::Colon => {
+                                    questions -= 1;
+                                    Role::Plain
+                                }
+                                _ => Role::Plain,
+                            };
+                            self.token(token, role, if i == 0 { line } else { None }, depth + 1);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Blocks put their statements on their own lines, one level in, with
+    // the closing brace back at the block's own level. Empty blocks stay `{}`.
+    fn block(&mut self, block: &SyntaxNode, depth: usize, line: Option<usize>) {
+        let empty = match block.children.as_slice() {
+            [SyntaxElement::Token(open), SyntaxElement::Token(close)] => {
+                !has_comment(&open.trailing) && !has_comment(&close.leading)
+            }
+            _ => false,
+        };
+
+        for child in &block.children {
+            match child {
+                SyntaxElement::Node(statement) => self.statement(statement, depth + 1),
+                SyntaxElement::Token(token) if token.lexeme.token == Token::LeftBrace => {
+                    self.token(token, Role::Plain, line, depth + 1);
+                }
+                SyntaxElement::Token(token) => {
+                    let line = if empty { None } else { Some(depth) };
+                    self.token(token, Role::Plain, line, depth + 1);
+                }
+            }
+        }
+    }
+
+    // Writes a token with the comments before and after it. `line` asks for
+    // the token to start a line at that indent; otherwise it follows the
+    // token before it, and only a comment can push it onto a new line, at
+    // the `continuation` indent.
+    fn token(&mut self, token: &SyntaxToken, role: Role, line: Option<usize>, continuation: usize) {
+        self.leading(token, line, continuation);
+
+        let prefix = !self
+            .previous
+            .as_ref()
+            .is_some_and(|previous| previous.ends_operand);
+        let kind = &token.lexeme.token;
+        let unary = prefix
+            && matches!(
+                kind,
+                Token::Minus | Token::Bang | Token::PlusPlus | Token::MinusMinus
+            );
+        let ends_operand = match kind {
+            Token::Identifier(_)
+            | Token::Number(..)
+            | Token::String(..)
+            | Token::Interpolation(..)
+            | Token::True
+            | Token::False
+            | Token::Nil
+            | Token::This
+            | Token::Super
+            | Token::RightParen
+            | Token::RightBracket => true,
+            Token::PlusPlus | Token::MinusMinus => !prefix,
+            _ => role == Role::MapClose,
+        };
+
+        let glued = self.glued(kind, role, prefix)
+            && self
+                .previous
+                .as_ref()
+                .is_none_or(|previous| scans_apart(&previous.text, &token.text));
+        self.write(&token.text, glued);
+        self.previous = Some(Written {
+            token: kind.clone(),
+            text: token.text.clone(),
+            role,
+            unary,
+            ends_operand,
+        });
+
+        for trivia in &token.trailing {
+            match trivia.kind {
+                TriviaKind::Whitespace | TriviaKind::Newline => {}
+                TriviaKind::Comment | TriviaKind::DocComment => {
+                    self.write(&trivia.text, false);
+                    self.line_break(continuation, false);
+                }
+                TriviaKind::BlockComment | TriviaKind::Skipped => self.write(&trivia.text, false),
+            }
+        }
+    }
+
+    // Comments before a token go on their own lines, as they did in the
+    // source, keeping up to one blank line before each where there was one.
+    fn leading(&mut self, token: &SyntaxToken, line: Option<usize>, continuation: usize) {
+        let indent = line.unwrap_or(continuation);
+        let mut newlines = 0;
+
+        for trivia in &token.leading {
+            match trivia.kind {
+                TriviaKind::Whitespace => {}
+                TriviaKind::Newline => newlines += 1,
+                TriviaKind::Comment | TriviaKind::DocComment => {
+                    self.line_break(indent, line.is_some() && newlines > 1);
+                    self.write(&trivia.text, false);
+                    self.line_break(indent, false);
+                    newlines = 0;
+                }
+                TriviaKind::BlockComment | TriviaKind::Skipped => {
+                    if newlines > 0 || line.is_some() {
+                        self.line_break(indent, line.is_some() && newlines > 1);
+                    }
+                    self.write(&trivia.text, false);
+                    if newlines > 0 {
+                        self.line_break(indent, false);
+                    }
+                    newlines = 0;
+                }
+            }
+        }
+
+        if let Some(indent) = line {
+            self.line_break(indent, newlines > 1);
+        }
+    }
+
+    // Whether a token goes right after the one before it, with no space.
+    fn glued(&self, token: &Token, role: Role, prefix: bool) -> bool {
+        let Some(previous) = &self.previous else {
+            return true;
+        };
+
+        if matches!(
+            token,
+            Token::RightParen | Token::RightBracket | Token::Comma | Token::SemiColon | Token::Dot
+        ) || matches!(role, Role::MapColon | Role::MapClose)
+        {
+            return true;
+        }
+        if matches!(
+            previous.token,
+            Token::LeftParen | Token::LeftBracket | Token::Dot
+        ) || previous.role == Role::MapOpen
+            || previous.unary
+            || (previous.token == Token::LeftBrace && *token == Token::RightBrace)
+        {
+            return true;
+        }
+
+        // A `fun` expression can be called straight after its body. After
+        // any other `}`, a `(` starts a new statement on its own line.
+        if previous.token == Token::RightBrace && *token == Token::LeftParen {
+            return true;
+        }
+
+        // Calls, indexes and postfix operators follow their operand.
+        matches!(
+            token,
+            Token::LeftParen | Token::LeftBracket | Token::PlusPlus | Token::MinusMinus
+        ) && !prefix
+    }
+
+    fn line_break(&mut self, indent: usize, blank: bool) {
+        let blank = blank || self.pending.as_ref().is_some_and(|pending| pending.blank);
+        self.pending = Some(Break { indent, blank });
+    }
+
+    fn write(&mut self, text: &str, glued: bool) {
+        if let Some(pending) = self.pending.take() {
+            if !self.out.is_empty() {
+                let trimmed = self.out.trim_end_matches([' ', '\t']).len();
+                self.out.truncate(trimmed);
+                // No blank line straight after an opening brace.
+                if pending.blank && !self.out.ends_with('{') {
+                    self.out.push('\n');
+                }
+                self.out.push('\n');
+                self.out.push_str(&INDENT.repeat(pending.indent));
+            }
+        } else if !glued && !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push(' ');
+        }
+        self.out.push_str(text);
+    }
+}
+
+fn has_comment(trivia: &[cst::Trivia]) -> bool {
+    trivia
+        .iter()
+        .any(|trivia| !matches!(trivia.kind, TriviaKind::Whitespace | TriviaKind::Newline))
+}
+
+// Whether two tokens written with nothing between them still scan as those
+// two tokens, so `-` and `-x` don't run together into `--x`.
+fn scans_apart(left: &str, right: &str) -> bool {
+    let (lexemes, errors) = scan(&format!("{}{}", left, right), 1);
+    errors.is_empty() && lexemes.len() == 2 && {
+        let joined = format!("{}{}", left, right);
+        joined[lexemes[0].span.start..lexemes[0].span.end] == *left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+
+    const MESSY: &str = "// header
+
+
+var a=1;   // trailing
+/// doc for f
+fun f(x,y){if(x>y){return {\"k\":[1,2],\"t\":x?1:2};}else print -y;
+  var g = fun (z) { return z**2; };
+  print g(3)[0]; a++; --a; print a - -1;
+  for (var i=0;i<3;i+=1) { }
+  try { throw \"x\"; } catch (e) { print e; } finally { print \"f\"; }
+}
+var h = (a, b) => a + b;   /* inline */ var m = {};
+print \"${a +1} and ${ h(1,2) }\";
+var n = fun (x) { return x; } (1);
+";
+
+    #[test]
+    fn formatting_lays_out_a_messy_program() {
+        let expected = "// header
+
+var a = 1; // trailing
+/// doc for f
+fun f(x, y) {
+  if (x > y) {
+    return {\"k\": [1, 2], \"t\": x ? 1 : 2};
+  } else print -y;
+  var g = fun (z) {
+    return z ** 2;
+  };
+  print g(3)[0];
+  a++;
+  --a;
+  print a - -1;
+  for (var i = 0; i < 3; i += 1) {}
+  try {
+    throw \"x\";
+  } catch (e) {
+    print e;
+  } finally {
+    print \"f\";
+  }
+}
+var h = (a, b) => a + b; /* inline */
+var m = {};
+print \"${a +1} and ${ h(1,2) }\";
+var n = fun (x) {
+  return x;
+}(1);
+";
+        assert_eq!(format_source(MESSY).unwrap(), expected);
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let benches = root.join("benches");
+        let mut sources = vec![
+            MESSY.to_string(),
+            fs::read_to_string(root.join("test.lox")).unwrap(),
+        ];
+        for entry in fs::read_dir(benches).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|extension| extension == "lox") {
+                sources.push(fs::read_to_string(path).unwrap());
+            }
+        }
+
+        for source in sources {
+            let once = format_source(&source).unwrap();
+            assert_eq!(format_source(&once).unwrap(), once);
+        }
+    }
+
+    #[test]
+    fn programs_that_dont_parse_are_refused() {
+        assert!(format_source("var a = ;").is_err());
+        assert!(format_source("print # 1;").is_err());
+    }
+}
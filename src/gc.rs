@@ -0,0 +1,238 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::rc::{Rc, Weak};
+
+// Environments, closures and every other object that can hold a reference to
+// one of them are plain `Rc`s, so acyclic garbage is freed the moment its last
+// reference goes away. This heap only exists to find the cycles `Rc` can't.
+//
+// A collection works out the roots by itself instead of asking the
+// interpreter for them: every reference that one managed object holds to
+// another is counted, and any object with more strong references than that is
+// also held from the Rust stack, so it's live. Everything reachable from those
+// roots is kept, and the rest is only alive because of a cycle, so its
+// references are cleared to let `Rc` free it.
+
+const INITIAL_THRESHOLD: usize = 1024;
+
+thread_local! {
+    static HEAP: RefCell<Heap> = RefCell::new(Heap::new());
+}
+
+pub trait Trace {
+    /// Reports every managed object this one holds a strong reference to.
+    /// Objects that are in use and can't be inspected call `tracer.busy()`.
+    fn trace(&self, tracer: &mut Tracer);
+
+    /// Drops the references reported by `trace`. Only called on garbage, and
+    /// only needed by objects that can form a cycle on their own.
+    fn clear(&self) {}
+}
+
+pub struct Tracer<'a> {
+    visit: &'a mut dyn FnMut(*const ()),
+    busy: bool,
+}
+
+impl Tracer<'_> {
+    pub fn visit<T: ?Sized>(&mut self, object: &Rc<T>) {
+        (self.visit)(Rc::as_ptr(object) as *const ());
+    }
+
+    pub fn busy(&mut self) {
+        self.busy = true;
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct GcStats {
+    pub allocations: usize,
+    pub collections: usize,
+    pub freed: usize,
+    pub live: usize,
+    pub peak: usize,
+}
+
+impl Display for GcStats {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "[gc] allocations: {}, collections: {}, freed: {}, live: {}, peak: {}",
+            self.allocations, self.collections, self.freed, self.live, self.peak
+        )
+    }
+}
+
+struct Heap {
+    objects: Vec<Weak<dyn Trace>>,
+    stats: GcStats,
+    stress: bool,
+    threshold: usize,
+    since_collection: usize,
+}
+
+impl Heap {
+    fn new() -> Heap {
+        Heap {
+            objects: Vec::new(),
+            stats: GcStats::default(),
+            stress: cfg!(feature = "gc_stress"),
+            threshold: INITIAL_THRESHOLD,
+            since_collection: 0,
+        }
+    }
+}
+
+/// Registers a newly allocated object with the collector. May run a
+/// collection first, so callers must keep `object` alive across the call.
+pub fn manage<T: Trace + 'static>(object: &Rc<T>) {
+    let object: Rc<dyn Trace> = object.clone();
+
+    let collect = HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.objects.push(Rc::downgrade(&object));
+        heap.stats.allocations += 1;
+        heap.since_collection += 1;
+        heap.stats.peak = heap.stats.peak.max(heap.objects.len());
+        heap.stress || heap.since_collection >= heap.threshold
+    });
+
+    drop(object);
+
+    if collect {
+        self::collect();
+    }
+}
+
+/// Collects on every allocation, for shaking out missing references.
+pub fn set_stress(stress: bool) {
+    HEAP.with(|heap| heap.borrow_mut().stress = stress);
+}
+
+pub fn stats() -> GcStats {
+    HEAP.with(|heap| {
+        let heap = heap.borrow();
+        let mut stats = heap.stats.clone();
+        stats.live = heap.objects.iter().filter(|o| o.strong_count() > 0).count();
+        stats
+    })
+}
+
+pub fn collect() {
+    let objects: Vec<Rc<dyn Trace>> = HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        let objects: Vec<Rc<dyn Trace>> = heap.objects.iter().filter_map(Weak::upgrade).collect();
+        heap.objects.clear();
+        objects
+    });
+
+    let index: HashMap<*const (), usize> = objects
+        .iter()
+        .enumerate()
+        .map(|(i, object)| (Rc::as_ptr(object) as *const (), i))
+        .collect();
+
+    let mut internal = vec![0; objects.len()];
+    let mut edges: Vec<Vec<usize>> = vec![Vec::new(); objects.len()];
+    let mut busy = vec![false; objects.len()];
+
+    for (i, object) in objects.iter().enumerate() {
+        let mut visit = |ptr: *const ()| {
+            if let Some(&j) = index.get(&ptr) {
+                internal[j] += 1;
+                edges[i].push(j);
+            }
+        };
+        let mut tracer = Tracer {
+            visit: &mut visit,
+            busy: false,
+        };
+        object.trace(&mut tracer);
+        busy[i] = tracer.busy;
+    }
+
+    // One strong reference is the upgrade held in `objects`.
+    let mut stack: Vec<usize> = (0..objects.len())
+        .filter(|&i| busy[i] || Rc::strong_count(&objects[i]) - 1 > internal[i])
+        .collect();
+
+    let mut marked = vec![false; objects.len()];
+    while let Some(i) = stack.pop() {
+        if !marked[i] {
+            marked[i] = true;
+            stack.extend(edges[i].iter().copied());
+        }
+    }
+
+    let mut freed = 0;
+    for (i, object) in objects.iter().enumerate() {
+        if !marked[i] {
+            object.clear();
+            freed += 1;
+        }
+    }
+
+    let survivors: Vec<Weak<dyn Trace>> = objects
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| marked[i])
+        .map(|(_, object)| Rc::downgrade(object))
+        .collect();
+
+    drop(objects);
+
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        let live = survivors.len();
+        // Anything allocated while clearing garbage was appended meanwhile.
+        let allocated = std::mem::take(&mut heap.objects);
+        heap.objects = survivors;
+        heap.objects.extend(allocated);
+        heap.stats.collections += 1;
+        heap.stats.freed += freed;
+        heap.since_collection = 0;
+        heap.threshold = INITIAL_THRESHOLD.max(live * 2);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::environment::{Env, Environment, Function, Symbol};
+    use crate::enums::statement::Statement;
+
+    // A function defined in `environment`, which then holds the function
+    // that captured it.
+    fn closure_cycle() -> Env {
+        let environment = Environment::new();
+        let function = Function::new(
+            "f".to_string(),
+            Vec::new(),
+            Statement::Block(Vec::new()),
+            environment.clone(),
+        );
+        environment
+            .borrow_mut()
+            .define("f".to_string(), Symbol::Function(function));
+        environment
+    }
+
+    #[test]
+    fn unreachable_closure_cycles_are_collected() {
+        let environment = closure_cycle();
+        let weak = Rc::downgrade(&environment);
+        drop(environment);
+        assert!(weak.upgrade().is_some(), "the cycle keeps itself alive");
+
+        collect();
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn reachable_closure_cycles_survive() {
+        let environment = closure_cycle();
+        collect();
+        assert!(environment.borrow().get("f").is_some());
+    }
+}
@@ -0,0 +1,468 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::process;
+
+use crate::cst::{self, Trivia, TriviaKind};
+use crate::enums::token::{KEYWORD_MAP, Lexeme, Span, StringPart, Token};
+use crate::json::Json;
+use crate::natives;
+use crate::parser::parse_source;
+use crate::scope::{self, Analysis, Kind};
+use crate::utils::{flag_value, get_file_contents};
+
+/// What a piece of source text is, for colouring it. Identifiers are split by
+/// what they resolve to. `class` is reserved but nothing declares classes
+/// yet, so no identifier is ever one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Class {
+    Keyword,
+    Variable,
+    Function,
+    Parameter,
+    Number,
+    String,
+    Operator,
+    Comment,
+    /// Text the tokenizer rejected.
+    Error,
+}
+
+impl Class {
+    pub fn name(self) -> &'static str {
+        match self {
+            Class::Keyword => "keyword",
+            Class::Variable => "variable",
+            Class::Function => "function",
+            Class::Parameter => "parameter",
+            Class::Number => "number",
+            Class::String => "string",
+            Class::Operator => "operator",
+            Class::Comment => "comment",
+            Class::Error => "error",
+        }
+    }
+
+    // Operators are left in the terminal's own colour.
+    fn ansi(self) -> Option<&'static str> {
+        match self {
+            Class::Keyword => Some("35"),
+            Class::Variable => Some("36"),
+            Class::Function => Some("34"),
+            Class::Parameter => Some("3;36"),
+            Class::Number => Some("33"),
+            Class::String => Some("32"),
+            Class::Operator => None,
+            Class::Comment => Some("90"),
+            Class::Error => Some("4;31"),
+        }
+    }
+}
+
+impl Display for Class {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.name())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Highlight {
+    pub class: Class,
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Ansi,
+    Html,
+    Json,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "ansi" => Some(Format::Ansi),
+            "html" => Some(Format::Html),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Runs `highlight <file>`, printing the program as ANSI-coloured text, or
+/// with `--format html` or `--format json` as HTML spans or JSON ranges.
+pub fn highlight(filename: &str, flags: &[String]) {
+    let format = match flag_value(flags, "--format") {
+        None => Format::Ansi,
+        Some(name) => Format::from_name(name).unwrap_or_else(|| {
+            eprintln!("Unknown highlight format: {}", name);
+            process::exit(64);
+        }),
+    };
+
+    let source = get_file_contents(filename);
+    print!("{}", render(&source, format));
+}
+
+pub fn render(source: &str, format: Format) -> String {
+    let highlights = classify(source);
+    match format {
+        Format::Ansi => ansi(source, &highlights),
+        Format::Html => html(source, &highlights),
+        Format::Json => format!("{}\n", json(source, &highlights)),
+    }
+}
+
+/// Classifies every lexeme and comment in `source`, in order. Whitespace is
+/// left out. Broken programs are still classified: rejected text is an
+/// error, and names are resolved as far as the program parses.
+pub fn classify(source: &str) -> Vec<Highlight> {
+    let (statements, _) = parse_source(source);
+    let analysis = scope::analyze(&statements, source);
+    let names = Names::new(&analysis);
+
+    let mut highlights = Vec::new();
+    let mut position = 0;
+    for token in cst::lossless_tokens(source) {
+        trivia(&token.leading, &mut position, &mut highlights);
+        if token.lexeme.token != Token::EOF {
+            lexeme(&token.lexeme, source, &names, &mut highlights);
+        }
+        position = token.lexeme.span.end;
+        trivia(&token.trailing, &mut position, &mut highlights);
+    }
+
+    highlights
+}
+
+// Comments and rejected text, from `position` on. Whitespace only moves
+// `position` along.
+fn trivia(trivia: &[Trivia], position: &mut usize, highlights: &mut Vec<Highlight>) {
+    for trivia in trivia {
+        let span = Span {
+            start: *position,
+            end: *position + trivia.text.len(),
+        };
+        *position = span.end;
+        let class = match trivia.kind {
+            TriviaKind::Whitespace | TriviaKind::Newline => continue,
+            TriviaKind::Comment | TriviaKind::DocComment | TriviaKind::BlockComment => {
+                Class::Comment
+            }
+            TriviaKind::Skipped => Class::Error,
+        };
+        highlights.push(Highlight { class, span });
+    }
+}
+
+// What each mention of a name refers to, by where the mention starts.
+struct Names<'a> {
+    analysis: &'a Analysis,
+    mentions: HashMap<usize, usize>,
+}
+
+impl<'a> Names<'a> {
+    fn new(analysis: &'a Analysis) -> Names<'a> {
+        let mentions = analysis
+            .references
+            .iter()
+            .map(|(span, definition)| (span.start, *definition))
+            .collect();
+        Names { analysis, mentions }
+    }
+
+    fn class(&self, name: &str, span: Span) -> Class {
+        let Some(&definition) = self.mentions.get(&span.start) else {
+            // Unresolved names are natives or globals from elsewhere.
+            return if natives::names().contains(&name) {
+                Class::Function
+            } else {
+                Class::Variable
+            };
+        };
+
+        let definition = &self.analysis.definitions[definition];
+        match definition.kind {
+            Kind::Function => Class::Function,
+            Kind::Parameter => Class::Parameter,
+            Kind::Variable | Kind::Constant if definition.params.is_some() => Class::Function,
+            Kind::Variable | Kind::Constant => Class::Variable,
+        }
+    }
+}
+
+fn lexeme(lexeme: &Lexeme, source: &str, names: &Names, highlights: &mut Vec<Highlight>) {
+    let class = match &lexeme.token {
+        Token::Identifier(name) => names.class(name, lexeme.span),
+        Token::Number(..) => Class::Number,
+        Token::String(..) => Class::String,
+        Token::Error(..) | Token::ErrorString(..) | Token::LexError(..) | Token::Unknown => {
+            Class::Error
+        }
+        Token::Interpolation(_, parts) => {
+            interpolation(lexeme.span, parts, source, names, highlights);
+            return;
+        }
+        token if KEYWORD_MAP.values().any(|keyword| keyword == token) => Class::Keyword,
+        _ => Class::Operator,
+    };
+
+    highlights.push(Highlight {
+        class,
+        span: lexeme.span,
+    });
+}
+
+// The literal text of an interpolated string is string and the `${` and `}`
+// around each piece of code are operators. The code inside is classified
+// like any other, comments and all.
+fn interpolation(
+    span: Span,
+    parts: &[StringPart],
+    source: &str,
+    names: &Names,
+    highlights: &mut Vec<Highlight>,
+) {
+    let mut position = span.start;
+    for code in parts.iter().filter_map(|part| match part {
+        StringPart::Code(code) => Some(code),
+        StringPart::Text(_) => None,
+    }) {
+        let open = position + code_start(&source[position..span.end]);
+        push(Class::String, position, open, highlights);
+        push(Class::Operator, open, open + 2, highlights);
+        position = open + 2;
+
+        for inner in code {
+            code_trivia(source, position, inner.span.start, highlights);
+            lexeme(inner, source, names, highlights);
+            position = inner.span.end;
+        }
+
+        // The code's lexemes are balanced, so the next `}` closes it.
+        let close = source[position..span.end]
+            .find('}')
+            .map_or(span.end, |i| position + i);
+        code_trivia(source, position, close, highlights);
+        push(Class::Operator, close, close + 1, highlights);
+        position = close + 1;
+    }
+    push(Class::String, position, span.end, highlights);
+}
+
+// Where the first `${` in a string's literal text starts, past escapes.
+fn code_start(text: &str) -> usize {
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '$' if text[i + 1..].starts_with('{') => return i,
+            _ => {}
+        }
+    }
+    text.len()
+}
+
+// The whitespace and comments between the lexemes of a piece of code.
+fn code_trivia(source: &str, start: usize, end: usize, highlights: &mut Vec<Highlight>) {
+    let mut position = start;
+    for token in cst::lossless_tokens(&source[start..end]) {
+        trivia(&token.leading, &mut position, highlights);
+        trivia(&token.trailing, &mut position, highlights);
+    }
+}
+
+fn push(class: Class, start: usize, end: usize, highlights: &mut Vec<Highlight>) {
+    if start < end {
+        highlights.push(Highlight {
+            class,
+            span: Span { start, end },
+        });
+    }
+}
+
+// Walks the source, handing each highlighted piece and each unhighlighted
+// gap between them to `write`.
+fn pieces(source: &str, highlights: &[Highlight], mut write: impl FnMut(Option<Class>, &str)) {
+    let mut position = 0;
+    for highlight in highlights {
+        if position < highlight.span.start {
+            write(None, &source[position..highlight.span.start]);
+        }
+        write(
+            Some(highlight.class),
+            &source[highlight.span.start..highlight.span.end],
+        );
+        position = highlight.span.end;
+    }
+    if position < source.len() {
+        write(None, &source[position..]);
+    }
+}
+
+pub fn ansi(source: &str, highlights: &[Highlight]) -> String {
+    let mut output = String::new();
+    pieces(source, highlights, |class, text| {
+        match class.and_then(Class::ansi) {
+            // Colour each line on its own, so a block comment spanning
+            // lines doesn't leave the colour on when output is cut short.
+            Some(code) => {
+                for (i, line) in text.split('\n').enumerate() {
+                    if i > 0 {
+                        output.push('\n');
+                    }
+                    if !line.is_empty() {
+                        output.push_str(&format!("\x1b[{}m{}\x1b[0m", code, line));
+                    }
+                }
+            }
+            None => output.push_str(text),
+        }
+    });
+    output
+}
+
+/// The program as HTML, each highlighted piece in a span whose class is
+/// its kind, inside a `<pre class="lox">`. The styles are left to the page.
+pub fn html(source: &str, highlights: &[Highlight]) -> String {
+    let mut output = String::from("<pre class=\"lox\">");
+    pieces(source, highlights, |class, text| {
+        let text = escape(text);
+        match class {
+            Some(class) => output.push_str(&format!("<span class=\"{}\">{}</span>", class, text)),
+            None => output.push_str(&text),
+        }
+    });
+    output.push_str("</pre>\n");
+    output
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// The highlights as a JSON array of ranges, each with its kind, byte
+/// offsets, and 1-based line.
+pub fn json(source: &str, highlights: &[Highlight]) -> Json {
+    let starts: Vec<usize> = std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+
+    Json::Array(
+        highlights
+            .iter()
+            .map(|highlight| {
+                Json::object([
+                    ("kind", highlight.class.name().into()),
+                    ("start", highlight.span.start.into()),
+                    ("end", highlight.span.end.into()),
+                    (
+                        "line",
+                        starts
+                            .partition_point(|&start| start <= highlight.span.start)
+                            .into(),
+                    ),
+                ])
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each highlighted piece of `source`, with its class.
+    fn highlighted(source: &str) -> Vec<(Class, &str)> {
+        classify(source)
+            .into_iter()
+            .map(|highlight| {
+                (
+                    highlight.class,
+                    &source[highlight.span.start..highlight.span.end],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn names_are_classified_by_what_they_resolve_to() {
+        let source = "fun f(p) { return p; }\nvar v = f(1);\nprint len(v);";
+        let names: Vec<_> = highlighted(source)
+            .into_iter()
+            .filter(|(class, _)| {
+                matches!(class, Class::Function | Class::Parameter | Class::Variable)
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (Class::Function, "f"),
+                (Class::Parameter, "p"),
+                (Class::Parameter, "p"),
+                (Class::Variable, "v"),
+                (Class::Function, "f"),
+                (Class::Function, "len"),
+                (Class::Variable, "v"),
+            ]
+        );
+    }
+
+    #[test]
+    fn comments_literals_and_rejected_text_are_classified() {
+        assert_eq!(
+            highlighted("// note\nprint 1 + \"s\"; @"),
+            [
+                (Class::Comment, "// note"),
+                (Class::Keyword, "print"),
+                (Class::Number, "1"),
+                (Class::Operator, "+"),
+                (Class::String, "\"s\""),
+                (Class::Operator, ";"),
+                (Class::Error, "@"),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_the_literal_text_of_an_interpolation_is_string() {
+        let source = "var x = 1;\nprint \"a \\${x} ${ x /* c */ } b\";";
+        let line: Vec<_> = highlighted(source).into_iter().skip(6).collect();
+        assert_eq!(
+            line,
+            [
+                (Class::String, "\"a \\${x} "),
+                (Class::Operator, "${"),
+                (Class::Variable, "x"),
+                (Class::Comment, "/* c */"),
+                (Class::Operator, "}"),
+                (Class::String, " b\""),
+                (Class::Operator, ";"),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_interpolations_are_classified_inside_out() {
+        let source = "print \"${ \"in ${1}\" }\";";
+        assert_eq!(
+            highlighted(source),
+            [
+                (Class::Keyword, "print"),
+                (Class::String, "\""),
+                (Class::Operator, "${"),
+                (Class::String, "\"in "),
+                (Class::Operator, "${"),
+                (Class::Number, "1"),
+                (Class::Operator, "}"),
+                (Class::String, "\""),
+                (Class::Operator, "}"),
+                (Class::String, "\""),
+                (Class::Operator, ";"),
+            ]
+        );
+    }
+}
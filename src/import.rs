@@ -0,0 +1,185 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::enums::environment::{Env, Environment, Symbol, unpack};
+use crate::enums::error::Error;
+use crate::enums::lox_string::LoxString;
+use crate::enums::map::{Key, Map, OrderedMap};
+use crate::evaluator::Value;
+use crate::parser::parse_statements;
+use crate::resolver::resolve;
+use crate::run::{ControlFlow, evaluate_statements};
+
+thread_local! {
+    // The exports of every module that has finished running, by canonical
+    // path, so each module runs once however often it's imported.
+    static LOADED: RefCell<HashMap<PathBuf, Map>> = RefCell::new(HashMap::new());
+    // The files being run, innermost last: the script itself, then any
+    // imports still in progress.
+    static RUNNING: RefCell<Vec<PathBuf>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Forgets every loaded module and makes `filename` the running script, so
+/// its imports are looked up next to it.
+pub fn reset(filename: &str) {
+    LOADED.with(|loaded| loaded.borrow_mut().clear());
+    RUNNING.with(|running| *running.borrow_mut() = vec![canonical(Path::new(filename))]);
+}
+
+/// Runs `import "path";` or `import "path" as alias;`. Without an alias, the
+/// module's exports are defined directly in `environment`; with one, they're
+/// bound to `alias` as a map, so they're read as `alias.name`.
+///
+/// A module runs in its own global environment. Its exports are its
+/// top-level variables, constants and functions, except those whose names
+/// start with an underscore. Constants and functions stay read-only in the
+/// importer, by name or through the alias.
+pub fn import(
+    path: &str,
+    alias: Option<String>,
+    line: usize,
+    environment: &mut Env,
+) -> Result<(), Error> {
+    let exports = load(&locate(path, line)?, line)?;
+
+    match alias {
+        Some(alias) => environment
+            .borrow_mut()
+            .define(alias, Symbol::variable(Value::Map(exports))),
+        None => {
+            let exports = exports.borrow();
+            for (key, value) in exports.iter() {
+                let Value::String(name) = key.to_value() else {
+                    continue;
+                };
+                let symbol = match (exports.fixed(key), value) {
+                    (Some("function"), Value::Function(function)) => {
+                        Symbol::Function(function.clone())
+                    }
+                    (Some(_), value) => Symbol::constant(value.clone()),
+                    (None, value) => Symbol::variable(value.clone()),
+                };
+                environment.borrow_mut().define(name.to_string(), symbol);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Imports are looked up relative to the importing file first, then in each
+// directory listed in `LOX_PATH`.
+fn locate(path: &str, line: usize) -> Result<PathBuf, Error> {
+    let importer = RUNNING.with(|running| running.borrow().last().cloned());
+    let base = importer
+        .as_deref()
+        .and_then(Path::parent)
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    let search_path = env::var_os("LOX_PATH")
+        .map(|paths| env::split_paths(&paths).collect::<Vec<PathBuf>>())
+        .unwrap_or_default();
+
+    std::iter::once(base)
+        .chain(search_path)
+        .map(|dir| dir.join(path))
+        .find(|candidate| candidate.is_file())
+        .map(|found| canonical(&found))
+        .ok_or_else(|| Error::RuntimeError(line, format!("Can't find module '{}'.", path)))
+}
+
+fn load(path: &Path, line: usize) -> Result<Map, Error> {
+    if let Some(exports) = LOADED.with(|loaded| loaded.borrow().get(path).cloned()) {
+        return Ok(exports);
+    }
+
+    let cycle = RUNNING.with(|running| {
+        let running = running.borrow();
+        let start = running.iter().position(|file| file == path)?;
+        let chain = running[start..]
+            .iter()
+            .chain(std::iter::once(&path.to_path_buf()))
+            .map(|file| file.display().to_string())
+            .collect::<Vec<String>>();
+        Some(chain.join(" -> "))
+    });
+    if let Some(cycle) = cycle {
+        return Err(Error::RuntimeError(
+            line,
+            format!("Circular import: {}.", cycle),
+        ));
+    }
+
+    RUNNING.with(|running| running.borrow_mut().push(path.to_path_buf()));
+    let result = execute(path, line);
+    RUNNING.with(|running| running.borrow_mut().pop());
+
+    let exports = result?;
+    LOADED.with(|loaded| {
+        loaded
+            .borrow_mut()
+            .insert(path.to_path_buf(), exports.clone())
+    });
+    Ok(exports)
+}
+
+fn execute(path: &Path, line: usize) -> Result<Map, Error> {
+    let (statements, errors) = parse_statements(&path.display().to_string());
+    if !errors.is_empty() {
+        return Err(Error::RuntimeError(
+            line,
+            format!("Couldn't parse module '{}'.", path.display()),
+        ));
+    }
+    if let Some(error) = resolve(&statements).into_iter().next() {
+        return Err(Error::RuntimeError(
+            line,
+            format!("In module '{}': {}", path.display(), error),
+        ));
+    }
+
+    let mut module_env = Environment::new();
+    match evaluate_statements(statements, &mut module_env) {
+        Ok(()) => {}
+        Err(ControlFlow::Runtime(error)) => return Err(error),
+        Err(ControlFlow::Return(_)) | Err(ControlFlow::TailCall(_)) => {
+            return Err(Error::RuntimeError(
+                line,
+                format!("Can't return from top-level code in '{}'.", path.display()),
+            ));
+        }
+    }
+
+    Ok(exports(&module_env))
+}
+
+fn exports(module_env: &Env) -> Map {
+    let module_env = module_env.borrow();
+    let mut names: Vec<&String> = module_env
+        .symbols
+        .keys()
+        .filter(|name| !name.starts_with('_'))
+        .collect();
+    // Sorted, so a module prints the same way every time.
+    names.sort();
+
+    let mut exports = OrderedMap::default();
+    for name in names {
+        let key = Key::String(LoxString::from(name.as_str()));
+        match &module_env.symbols[name] {
+            Symbol::Variable(slot) => exports.insert(key, unpack(slot)),
+            Symbol::Constant(slot) => exports.insert_fixed(key, unpack(slot), "constant"),
+            Symbol::Function(function) => {
+                exports.insert_fixed(key, Value::Function(function.clone()), "function")
+            }
+        }
+    }
+    Map::new(exports)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
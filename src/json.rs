@@ -0,0 +1,291 @@
+use std::fmt::{self, Display, Write};
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A JSON value, for the language server's messages and the `highlight`
+/// command's output. Objects keep their keys in the order they were written.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object<const N: usize>(entries: [(&str, Json); N]) -> Json {
+        Json::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// The value under `key`, if this is an object that has it.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(entries) => entries
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(number) if *number >= 0.0 && number.fract() == 0.0 => {
+                Some(*number as usize)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(bool) => Some(*bool),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut chars = text.chars().peekable();
+        let value = value(&mut chars)?;
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None => Ok(value),
+            Some(c) => Err(format!("Unexpected '{}' after JSON value.", c)),
+        }
+    }
+}
+
+impl From<&str> for Json {
+    fn from(string: &str) -> Json {
+        Json::String(string.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(string: String) -> Json {
+        Json::String(string)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(number: usize) -> Json {
+        Json::Number(number as f64)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(bool: bool) -> Json {
+        Json::Bool(bool)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(items: Vec<Json>) -> Json {
+        Json::Array(items)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Json {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+type Input<'a> = Peekable<Chars<'a>>;
+
+fn skip_whitespace(chars: &mut Input) {
+    while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+}
+
+fn value(chars: &mut Input) -> Result<Json, String> {
+    skip_whitespace(chars);
+
+    match chars.peek() {
+        Some('{') => {
+            chars.next();
+            let mut entries = Vec::new();
+            skip_whitespace(chars);
+            if chars.next_if_eq(&'}').is_some() {
+                return Ok(Json::Object(entries));
+            }
+            loop {
+                skip_whitespace(chars);
+                let key = string(chars)?;
+                skip_whitespace(chars);
+                expect(chars, ':')?;
+                entries.push((key, value(chars)?));
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => {}
+                    Some('}') => return Ok(Json::Object(entries)),
+                    _ => return Err("Expected ',' or '}' in object.".to_string()),
+                }
+            }
+        }
+        Some('[') => {
+            chars.next();
+            let mut items = Vec::new();
+            skip_whitespace(chars);
+            if chars.next_if_eq(&']').is_some() {
+                return Ok(Json::Array(items));
+            }
+            loop {
+                items.push(value(chars)?);
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => {}
+                    Some(']') => return Ok(Json::Array(items)),
+                    _ => return Err("Expected ',' or ']' in array.".to_string()),
+                }
+            }
+        }
+        Some('"') => Ok(Json::String(string(chars)?)),
+        Some('t') => word(chars, "true", Json::Bool(true)),
+        Some('f') => word(chars, "false", Json::Bool(false)),
+        Some('n') => word(chars, "null", Json::Null),
+        Some(c) if *c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(c) =
+                chars.next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+            {
+                number.push(c);
+            }
+            number
+                .parse()
+                .map(Json::Number)
+                .map_err(|_| format!("Invalid number '{}'.", number))
+        }
+        Some(c) => Err(format!("Unexpected '{}' in JSON.", c)),
+        None => Err("Unexpected end of JSON.".to_string()),
+    }
+}
+
+fn expect(chars: &mut Input, expected: char) -> Result<(), String> {
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        _ => Err(format!("Expected '{}' in JSON.", expected)),
+    }
+}
+
+fn word(chars: &mut Input, word: &str, value: Json) -> Result<Json, String> {
+    for expected in word.chars() {
+        expect(chars, expected)?;
+    }
+    Ok(value)
+}
+
+fn string(chars: &mut Input) -> Result<String, String> {
+    expect(chars, '"')?;
+    let mut string = String::new();
+
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(string),
+            Some('\\') => match chars.next() {
+                Some('"') => string.push('"'),
+                Some('\\') => string.push('\\'),
+                Some('/') => string.push('/'),
+                Some('b') => string.push('\u{8}'),
+                Some('f') => string.push('\u{c}'),
+                Some('n') => string.push('\n'),
+                Some('r') => string.push('\r'),
+                Some('t') => string.push('\t'),
+                Some('u') => {
+                    let high = hex(chars)?;
+                    // Characters outside the basic plane come as a pair of
+                    // UTF-16 surrogates.
+                    let code = if (0xD800..0xDC00).contains(&high) {
+                        expect(chars, '\\')?;
+                        expect(chars, 'u')?;
+                        let low = hex(chars)?;
+                        0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF)
+                    } else {
+                        high
+                    };
+                    string.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                }
+                _ => return Err("Invalid escape in JSON string.".to_string()),
+            },
+            Some(c) => string.push(c),
+            None => return Err("Unterminated JSON string.".to_string()),
+        }
+    }
+}
+
+fn hex(chars: &mut Input) -> Result<u32, String> {
+    let digits: String = (0..4).filter_map(|_| chars.next()).collect();
+    u32::from_str_radix(&digits, 16).map_err(|_| format!("Invalid escape '\\u{}'.", digits))
+}
+
+impl Display for Json {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(fmt, "null"),
+            Json::Bool(bool) => write!(fmt, "{}", bool),
+            Json::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => {
+                write!(fmt, "{}", *number as i64)
+            }
+            Json::Number(number) if number.is_finite() => write!(fmt, "{}", number),
+            Json::Number(_) => write!(fmt, "null"),
+            Json::String(string) => write_string(fmt, string),
+            Json::Array(items) => {
+                write!(fmt, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(fmt, ",")?;
+                    }
+                    write!(fmt, "{}", item)?;
+                }
+                write!(fmt, "]")
+            }
+            Json::Object(entries) => {
+                write!(fmt, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(fmt, ",")?;
+                    }
+                    write_string(fmt, key)?;
+                    write!(fmt, ":{}", value)?;
+                }
+                write!(fmt, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(fmt: &mut fmt::Formatter, string: &str) -> fmt::Result {
+    fmt.write_char('"')?;
+    for c in string.chars() {
+        match c {
+            '"' => fmt.write_str("\\\"")?,
+            '\\' => fmt.write_str("\\\\")?,
+            '\n' => fmt.write_str("\\n")?,
+            '\r' => fmt.write_str("\\r")?,
+            '\t' => fmt.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(fmt, "\\u{:04x}", c as u32)?,
+            c => fmt.write_char(c)?,
+        }
+    }
+    fmt.write_char('"')
+}
@@ -0,0 +1,754 @@
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+use std::{fs, process};
+
+use crate::cst::{self, NodeKind, SyntaxElement, SyntaxNode, TriviaKind};
+use crate::enums::expression::{Expression, Operator, Primary, Unary};
+use crate::enums::statement::Statement;
+use crate::enums::token::{Span, Token};
+use crate::parser::parse_source;
+use crate::scope::{self, Analysis, Kind, Locator};
+use crate::tokenizer::scan;
+use crate::utils::{flag_value, get_file_contents};
+
+/// The file a linted script's settings are read from. It's looked for next
+/// to the script, then in each directory above it.
+const CONFIG_FILE: &str = ".loxlint";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rule {
+    UnusedVariable,
+    UnusedParameter,
+    Shadowing,
+    UndeclaredAssignment,
+    UnreachableCode,
+    MismatchedEquality,
+    EmptyBlock,
+    ConstantCondition,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 8] = [
+        Rule::UnusedVariable,
+        Rule::UnusedParameter,
+        Rule::Shadowing,
+        Rule::UndeclaredAssignment,
+        Rule::UnreachableCode,
+        Rule::MismatchedEquality,
+        Rule::EmptyBlock,
+        Rule::ConstantCondition,
+    ];
+
+    /// The name a rule is switched off by, in the config file or in a
+    /// `// lox-lint: allow(...)` comment.
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::UnusedVariable => "unused-variable",
+            Rule::UnusedParameter => "unused-parameter",
+            Rule::Shadowing => "shadowing",
+            Rule::UndeclaredAssignment => "undeclared-assignment",
+            Rule::UnreachableCode => "unreachable-code",
+            Rule::MismatchedEquality => "mismatched-equality",
+            Rule::EmptyBlock => "empty-block",
+            Rule::ConstantCondition => "constant-condition",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Rule::ALL.into_iter().find(|rule| rule.name() == name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Warning {
+    pub rule: Rule,
+    pub span: Span,
+    pub message: String,
+}
+
+impl Display for Warning {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "warning[{}]: {}", self.rule.name(), self.message)
+    }
+}
+
+/// Runs `lint <file>`, printing each warning with the source it points at.
+/// Exits with 1 if there were any warnings, and 65 if the file doesn't
+/// parse. `--config <file>` reads the rule settings from a given file
+/// instead of the nearest `.loxlint`.
+pub fn lint(filename: &str, flags: &[String]) {
+    let config = match flag_value(flags, "--config") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => find_config(Path::new(filename)),
+    };
+    let enabled = match config {
+        Some(path) => read_config(&path).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(64);
+        }),
+        None => Rule::ALL.into_iter().collect(),
+    };
+
+    let source = get_file_contents(filename);
+    let warnings = match lint_source(&source) {
+        Ok(warnings) => warnings,
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{}", error);
+            }
+            process::exit(65);
+        }
+    };
+
+    let lines = LineIndex::new(&source);
+    let mut reported = 0;
+    for warning in warnings
+        .iter()
+        .filter(|warning| enabled.contains(&warning.rule))
+    {
+        println!("{}", warning);
+        println!("{}", lines.excerpt(filename, &source, warning.span));
+        reported += 1;
+    }
+
+    if reported > 0 {
+        process::exit(1);
+    }
+}
+
+fn find_config(script: &Path) -> Option<PathBuf> {
+    let script = script.canonicalize().ok()?;
+    script
+        .ancestors()
+        .skip(1)
+        .map(|dir| dir.join(CONFIG_FILE))
+        .find(|config| config.is_file())
+}
+
+// A config file turns rules off or back on, one per line, as
+// `rule-name = allow` or `rule-name = warn`. `#` starts a comment.
+fn read_config(path: &Path) -> Result<HashSet<Rule>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|_| format!("Failed to read lint config {}", path.display()))?;
+    let mut enabled: HashSet<Rule> = Rule::ALL.into_iter().collect();
+
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let invalid = || {
+            format!(
+                "{}:{}: Invalid lint setting '{}'.",
+                path.display(),
+                i + 1,
+                line
+            )
+        };
+        let (name, level) = line.split_once('=').ok_or_else(invalid)?;
+        let rule = Rule::from_name(name.trim()).ok_or_else(|| {
+            format!(
+                "{}:{}: Unknown lint rule '{}'.",
+                path.display(),
+                i + 1,
+                name.trim()
+            )
+        })?;
+        match level.trim() {
+            "allow" => enabled.remove(&rule),
+            "warn" => enabled.insert(rule),
+            _ => return Err(invalid()),
+        };
+    }
+
+    Ok(enabled)
+}
+
+/// Every warning in a program, in source order, apart from those switched
+/// off by `// lox-lint: allow(rule, ...)` comments. A comment on its own line
+/// covers the line after it; one after code covers its own line. Programs
+/// that don't scan or parse aren't linted.
+pub fn lint_source(source: &str) -> Result<Vec<Warning>, Vec<String>> {
+    let (_, errors) = scan(source, 1);
+    if !errors.is_empty() {
+        return Err(errors.iter().map(Token::to_string).collect());
+    }
+    let (statements, errors) = parse_source(source);
+    if !errors.is_empty() {
+        return Err(errors.iter().map(|error| error.to_string()).collect());
+    }
+
+    let tree = cst::parse(source);
+    let mut warnings = Vec::new();
+    syntax(&tree, &mut warnings);
+    names(&scope::analyze(&statements, source), &mut warnings);
+    warnings.extend(Values::run(&statements, source));
+    warnings.sort_by_key(|warning| (warning.span.start, warning.span.end));
+
+    let lines = LineIndex::new(source);
+    let allowed = allowed(&tree, &lines);
+    warnings.retain(|warning| {
+        let line = lines.line(warning.span.start);
+        !allowed.contains(&(line, warning.rule))
+    });
+    Ok(warnings)
+}
+
+fn allowed(tree: &SyntaxNode, lines: &LineIndex) -> HashSet<(usize, Rule)> {
+    let mut allowed = HashSet::new();
+
+    for token in tree.tokens() {
+        let comments = |trivia: &[cst::Trivia]| -> Vec<Rule> {
+            trivia
+                .iter()
+                .filter(|trivia| trivia.kind == TriviaKind::Comment)
+                .flat_map(|comment| allow_comment(&comment.text))
+                .collect()
+        };
+
+        let line = lines.line(token.lexeme.span.start);
+        allowed.extend(
+            comments(&token.leading)
+                .into_iter()
+                .map(|rule| (line, rule)),
+        );
+        let line = lines.line(token.lexeme.span.end);
+        allowed.extend(
+            comments(&token.trailing)
+                .into_iter()
+                .map(|rule| (line, rule)),
+        );
+    }
+
+    allowed
+}
+
+// The rules named by a `// lox-lint: allow(a, b)` comment.
+fn allow_comment(comment: &str) -> Vec<Rule> {
+    comment
+        .trim_start_matches('/')
+        .trim()
+        .strip_prefix("lox-lint:")
+        .and_then(|rest| rest.trim().strip_prefix("allow("))
+        .and_then(|rest| rest.split_once(')'))
+        .map(|(names, _)| {
+            names
+                .split(',')
+                .filter_map(|name| Rule::from_name(name.trim()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Rules that only need the shape of the code: blocks with nothing in them,
+// and statements after a `return` or `throw` in the same block.
+fn syntax(node: &SyntaxNode, warnings: &mut Vec<Warning>) {
+    let statements: Vec<&SyntaxNode> = node
+        .children
+        .iter()
+        .filter_map(|child| match child {
+            SyntaxElement::Node(node) if node.kind == NodeKind::Statement => Some(node),
+            _ => None,
+        })
+        .collect();
+
+    let exit = statements.iter().position(|statement| {
+        statement
+            .tokens()
+            .first()
+            .is_some_and(|first| matches!(first.lexeme.token, Token::Return | Token::Throw))
+    });
+    if let Some(exit) = exit
+        && let (Some(first), Some(last)) = (statements.get(exit + 1), statements.last())
+    {
+        let keyword = match statements[exit].tokens()[0].lexeme.token {
+            Token::Throw => "throw",
+            _ => "return",
+        };
+        warnings.push(Warning {
+            rule: Rule::UnreachableCode,
+            span: Span {
+                start: first.span().start,
+                end: last.span().end,
+            },
+            message: format!("Unreachable code after '{}'.", keyword),
+        });
+    }
+
+    for (i, child) in node.children.iter().enumerate() {
+        let SyntaxElement::Node(child) = child else {
+            continue;
+        };
+        if child.kind == NodeKind::Block
+            && is_empty(child)
+            && !is_function_body(&node.children[..i])
+        {
+            warnings.push(Warning {
+                rule: Rule::EmptyBlock,
+                span: child.span(),
+                message: "Empty block.".to_string(),
+            });
+        }
+        syntax(child, warnings);
+    }
+}
+
+// A block with a comment in it isn't empty: the comment usually says why.
+fn is_empty(block: &SyntaxNode) -> bool {
+    match block.children.as_slice() {
+        [SyntaxElement::Token(open), SyntaxElement::Token(close)] => open
+            .trailing
+            .iter()
+            .chain(&close.leading)
+            .all(|trivia| matches!(trivia.kind, TriviaKind::Whitespace | TriviaKind::Newline)),
+        _ => false,
+    }
+}
+
+// Functions are allowed to do nothing, so a block after `fun (...)` or
+// `fun name(...)` isn't reported.
+fn is_function_body(before: &[SyntaxElement]) -> bool {
+    let token = |element: Option<&SyntaxElement>| match element {
+        Some(SyntaxElement::Token(token)) => Some(token.lexeme.token.clone()),
+        _ => None,
+    };
+
+    match before {
+        [.., SyntaxElement::Node(params)] if params.kind == NodeKind::Group => {
+            let rest = &before[..before.len() - 1];
+            match token(rest.last()) {
+                Some(Token::Fun) => true,
+                Some(Token::Identifier(_)) => {
+                    token(rest.len().checked_sub(2).and_then(|i| rest.get(i))) == Some(Token::Fun)
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+// Rules about names, from what the scope analysis found.
+fn names(analysis: &Analysis, warnings: &mut Vec<Warning>) {
+    for definition in &analysis.definitions {
+        let name = &definition.name;
+        if definition.shadows {
+            warnings.push(Warning {
+                rule: Rule::Shadowing,
+                span: definition.span,
+                message: format!("'{}' shadows a declaration in an enclosing scope.", name),
+            });
+        }
+
+        // Globals may be used by other files, and a leading underscore marks
+        // a name as unused on purpose.
+        if definition.used || definition.global || name.starts_with('_') {
+            continue;
+        }
+        let (rule, what) = match definition.kind {
+            Kind::Variable => (Rule::UnusedVariable, "variable"),
+            Kind::Constant => (Rule::UnusedVariable, "constant"),
+            Kind::Function => (Rule::UnusedVariable, "function"),
+            Kind::Parameter => (Rule::UnusedParameter, "parameter"),
+        };
+        warnings.push(Warning {
+            rule,
+            span: definition.span,
+            message: format!("Unused {} '{}'.", what, name),
+        });
+    }
+
+    for (name, span) in &analysis.undeclared {
+        warnings.push(Warning {
+            rule: Rule::UndeclaredAssignment,
+            span: *span,
+            message: format!("Assignment to undeclared variable '{}'.", name),
+        });
+    }
+}
+
+// Rules about values: conditions that can't change and comparisons that
+// can't succeed.
+struct Values {
+    locator: Locator,
+    warnings: Vec<Warning>,
+}
+
+impl Values {
+    fn run(statements: &[Statement], source: &str) -> Vec<Warning> {
+        let mut values = Values {
+            locator: Locator::new(source),
+            warnings: Vec::new(),
+        };
+        values.statements(statements);
+        values.warnings
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Block(statements) => self.statements(statements),
+            Statement::Declaration(_, expression, _)
+            | Statement::Const(_, expression, _)
+            | Statement::Expression(expression)
+            | Statement::Print(expression)
+            | Statement::Return(expression)
+            | Statement::Throw(expression, _) => self.expression(expression),
+            Statement::IfElse(condition, then, otherwise) => {
+                self.locator.find(|token| *token == Token::If);
+                self.condition(condition, false);
+                self.statement(then);
+                if let Some(otherwise) = otherwise {
+                    self.statement(otherwise);
+                }
+            }
+            Statement::While(condition, body) => {
+                self.locator.find(|token| *token == Token::While);
+                self.condition(condition, true);
+                self.statement(body);
+            }
+            Statement::For(initializer, condition, increment, body) => {
+                if let Some(initializer) = initializer {
+                    self.statement(initializer);
+                }
+                for expression in condition.iter().chain(increment) {
+                    self.expression(expression);
+                }
+                self.statement(body);
+            }
+            Statement::ForIn(_, iterable, body, _) => {
+                self.expression(iterable);
+                self.statement(body);
+            }
+            Statement::Fn(_, _, body, _) => self.statement(body),
+            Statement::Try(body, catch, finally) => {
+                self.statement(body);
+                if let Some((_, handler)) = catch {
+                    self.statement(handler);
+                }
+                if let Some(finally) = finally {
+                    self.statement(finally);
+                }
+            }
+            Statement::Import(..) => {}
+        }
+    }
+
+    // `while (true)` is how an endless loop is written, so it's let through.
+    fn condition(&mut self, condition: &Expression, looping: bool) {
+        let span = self.locator.condition();
+        self.expression(condition);
+
+        let endless = looping && *condition == Expression::Primary(Primary::True);
+        if let Some(truth) = truthiness(condition)
+            && !endless
+        {
+            self.warnings.push(Warning {
+                rule: Rule::ConstantCondition,
+                span,
+                message: format!("Condition is always {}.", truth),
+            });
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Binary(left, operator, right, _) => {
+                self.expression(left);
+                let equality = matches!(operator, Operator::EqualEqual | Operator::BangEqual);
+                let span = if equality {
+                    self.locator
+                        .find(|token| matches!(token, Token::EqualEqual | Token::BangEqual))
+                } else {
+                    Span::default()
+                };
+                self.expression(right);
+
+                if let (true, Some(left), Some(right)) =
+                    (equality, literal_type(left), literal_type(right))
+                    && left != right
+                {
+                    let (always, symbol) = match operator {
+                        Operator::EqualEqual => (false, "=="),
+                        _ => (true, "!="),
+                    };
+                    self.warnings.push(Warning {
+                        rule: Rule::MismatchedEquality,
+                        span,
+                        message: format!(
+                            "'{}' between {} and {} is always {}.",
+                            symbol, left, right, always
+                        ),
+                    });
+                }
+            }
+            Expression::Assignment(_, value, _) => self.expression(value),
+            Expression::CompoundAssignment(target, _, value, _) => {
+                self.expression(target);
+                self.expression(value);
+            }
+            Expression::Increment(target, _, _, _) => self.expression(target),
+            Expression::Unary(_, operand, _) => self.expression(operand),
+            Expression::Primary(Primary::Function(_, args, _)) => self.expressions(args),
+            Expression::Primary(Primary::Grouping(inner)) => self.expression(inner),
+            Expression::Primary(_) => {}
+            Expression::Conditional(condition, then, otherwise) => {
+                self.expression(condition);
+                self.expression(then);
+                self.expression(otherwise);
+            }
+            Expression::Lambda(_, body) => self.statement(body),
+            Expression::Call(callee, args, _) => {
+                self.expression(callee);
+                self.expressions(args);
+            }
+            Expression::Interpolation(parts) | Expression::List(parts) => self.expressions(parts),
+            Expression::Map(entries, _) => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            Expression::Index(object, index, _) => {
+                self.expression(object);
+                self.expression(index);
+            }
+            Expression::Get(object, _, _) => self.expression(object),
+            Expression::IndexAssignment(object, index, value, _) => {
+                self.expression(object);
+                self.expression(index);
+                self.expression(value);
+            }
+        }
+    }
+
+    fn expressions(&mut self, expressions: &[Expression]) {
+        for expression in expressions {
+            self.expression(expression);
+        }
+    }
+}
+
+// The type of a literal, for the values `evaluator::equal` never finds
+// equal across types.
+fn literal_type(expression: &Expression) -> Option<&'static str> {
+    match expression {
+        Expression::Primary(Primary::Number(_)) => Some("a number"),
+        Expression::Primary(Primary::String(_)) | Expression::Interpolation(_) => Some("a string"),
+        Expression::Primary(Primary::True | Primary::False) => Some("a boolean"),
+        Expression::Primary(Primary::Nil) => Some("nil"),
+        Expression::Primary(Primary::Grouping(inner)) => literal_type(inner),
+        Expression::Unary(Unary::Minus, operand, _) => {
+            literal_type(operand).filter(|kind| *kind == "a number")
+        }
+        Expression::Unary(Unary::Bang, ..) => Some("a boolean"),
+        Expression::List(_) => Some("a list"),
+        Expression::Map(..) => Some("a map"),
+        Expression::Lambda(..) => Some("a function"),
+        _ => None,
+    }
+}
+
+// Whether a condition is truthy no matter what, following
+// `evaluator::truthy`.
+fn truthiness(expression: &Expression) -> Option<bool> {
+    match expression {
+        Expression::Primary(Primary::Number(number)) => Some(*number != 0.0),
+        Expression::Primary(Primary::True) => Some(true),
+        Expression::Primary(Primary::False | Primary::Nil) => Some(false),
+        Expression::Primary(Primary::String(_))
+        | Expression::Interpolation(_)
+        | Expression::List(_)
+        | Expression::Map(..)
+        | Expression::Lambda(..) => Some(true),
+        Expression::Primary(Primary::Grouping(inner)) => truthiness(inner),
+        Expression::Unary(Unary::Bang, operand, _) => truthiness(operand).map(|truth| !truth),
+        _ => None,
+    }
+}
+
+// Turns byte offsets into line and column numbers, both counted from 1.
+struct LineIndex {
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    fn new(source: &str) -> LineIndex {
+        let starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        LineIndex { starts }
+    }
+
+    fn line(&self, offset: usize) -> usize {
+        self.starts.partition_point(|&start| start <= offset)
+    }
+
+    // The location of a span, and its first line with the span underlined.
+    fn excerpt(&self, filename: &str, source: &str, span: Span) -> String {
+        let line = self.line(span.start);
+        let start = self.starts[line - 1];
+        let text = source[start..].lines().next().unwrap_or_default();
+        let column = source[start..span.start].chars().count() + 1;
+        let width = source[span.start..span.end.min(start + text.len()).max(span.start)]
+            .chars()
+            .count()
+            .max(1);
+
+        let gutter = " ".repeat(line.to_string().len());
+        format!(
+            "{gutter}--> {}:{}:{}\n{gutter} |\n{} | {}\n{gutter} | {}{}\n",
+            filename,
+            line,
+            column,
+            line,
+            text,
+            " ".repeat(column - 1),
+            "^".repeat(width),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    // The rules `source` breaks, in source order.
+    fn rules(source: &str) -> Vec<Rule> {
+        lint_source(source)
+            .unwrap()
+            .into_iter()
+            .map(|warning| warning.rule)
+            .collect()
+    }
+
+    fn flags(rule: Rule, source: &str) -> bool {
+        rules(source).contains(&rule)
+    }
+
+    #[test]
+    fn unused_variable() {
+        assert!(flags(Rule::UnusedVariable, "{ var a = 1; }"));
+        assert!(!flags(Rule::UnusedVariable, "{ var a = 1; print a; }"));
+        assert!(!flags(Rule::UnusedVariable, "{ var _a = 1; }"));
+        assert!(!flags(Rule::UnusedVariable, "var global = 1;"));
+    }
+
+    #[test]
+    fn unused_parameter() {
+        assert!(flags(Rule::UnusedParameter, "fun f(a) {}\nprint f;"));
+        assert!(!flags(
+            Rule::UnusedParameter,
+            "fun f(a) { print a; }\nprint f;"
+        ));
+        assert!(!flags(Rule::UnusedParameter, "fun f(_a) {}\nprint f;"));
+    }
+
+    #[test]
+    fn shadowing() {
+        assert!(flags(
+            Rule::Shadowing,
+            "{ var a = 1; { var a = 2; print a; } print a; }"
+        ));
+        assert!(!flags(
+            Rule::Shadowing,
+            "{ var a = 1; print a; } { var a = 2; print a; }"
+        ));
+    }
+
+    #[test]
+    fn undeclared_assignment() {
+        assert!(flags(Rule::UndeclaredAssignment, "nowhere = 1;"));
+        assert!(flags(
+            Rule::UndeclaredAssignment,
+            "fun f() { total += 1; }\nprint f;"
+        ));
+        assert!(!flags(Rule::UndeclaredAssignment, "var a; a = 1;"));
+        // A global declared after the function that assigns it is fine.
+        assert!(!flags(
+            Rule::UndeclaredAssignment,
+            "fun f() { a = 1; }\nvar a;\nprint f;"
+        ));
+    }
+
+    #[test]
+    fn unreachable_code() {
+        assert!(flags(
+            Rule::UnreachableCode,
+            "fun f() { return 1; print 2; }\nprint f;"
+        ));
+        assert!(flags(
+            Rule::UnreachableCode,
+            "fun f() { throw 1; print 2; }\nprint f;"
+        ));
+        assert!(!flags(
+            Rule::UnreachableCode,
+            "fun f(a) { if (a) return 1; print 2; }\nprint f;"
+        ));
+    }
+
+    #[test]
+    fn mismatched_equality() {
+        assert!(flags(Rule::MismatchedEquality, "var a = 1 == \"1\";"));
+        assert!(flags(Rule::MismatchedEquality, "var a = true != nil;"));
+        assert!(!flags(Rule::MismatchedEquality, "var a = 1 == 2;"));
+        assert!(!flags(
+            Rule::MismatchedEquality,
+            "var b = 1;\nvar a = b == \"1\";"
+        ));
+    }
+
+    #[test]
+    fn empty_block() {
+        assert!(flags(Rule::EmptyBlock, "if (clock()) {}"));
+        assert!(!flags(Rule::EmptyBlock, "fun stub() {}\nprint stub;"));
+        assert!(!flags(Rule::EmptyBlock, "if (clock()) {\n  // not yet\n}"));
+    }
+
+    #[test]
+    fn constant_condition() {
+        assert!(flags(Rule::ConstantCondition, "if (1) print 1;"));
+        assert!(flags(Rule::ConstantCondition, "while (nil) print 1;"));
+        assert!(!flags(Rule::ConstantCondition, "while (true) print 1;"));
+        assert!(!flags(Rule::ConstantCondition, "if (clock()) print 1;"));
+    }
+
+    #[test]
+    fn allow_comments_switch_rules_off() {
+        // On its own line, a comment covers the line after it.
+        let source = "// lox-lint: allow(empty-block, constant-condition)\nif (1) {}";
+        assert!(rules(source).is_empty());
+        // After code, it covers its own line only.
+        let source = "if (1) print 1; // lox-lint: allow(constant-condition)\nif (1) print 2;";
+        assert_eq!(rules(source), [Rule::ConstantCondition]);
+        // Only the rules it names.
+        let source = "// lox-lint: allow(empty-block)\nif (1) {}";
+        assert_eq!(rules(source), [Rule::ConstantCondition]);
+    }
+
+    #[test]
+    fn config_files_allow_and_warn() {
+        let path = env::temp_dir().join(format!("lox-lint-test-{}", process::id()));
+        fs::write(&path, "# quieter\nempty-block = allow\nshadowing = warn\n").unwrap();
+        let enabled = read_config(&path).unwrap();
+        assert!(!enabled.contains(&Rule::EmptyBlock));
+        assert!(enabled.contains(&Rule::Shadowing));
+
+        fs::write(&path, "no-such-rule = allow\n").unwrap();
+        assert!(
+            read_config(&path)
+                .unwrap_err()
+                .contains("Unknown lint rule")
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
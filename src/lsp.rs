@@ -0,0 +1,632 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+
+use crate::cst::{self, NodeKind, SyntaxElement, SyntaxNode};
+use crate::enums::error::Error;
+use crate::enums::token::{KEYWORD_MAP, Span, Token};
+use crate::json::Json;
+use crate::natives;
+use crate::parser::parse_source;
+use crate::resolver::resolve;
+use crate::scope::{self, Analysis, Definition, Kind};
+use crate::tokenizer::{scan, scan_spanned};
+
+// Error codes from the JSON-RPC and LSP specifications.
+const INVALID_REQUEST: f64 = -32600.0;
+const METHOD_NOT_FOUND: f64 = -32601.0;
+
+/// Runs the `lsp` command: a language server speaking the Language Server
+/// Protocol over stdin and stdout, until the client sends `exit` or closes
+/// stdin. Documents are synced in full on every change.
+pub fn serve() {
+    let mut input = io::stdin().lock();
+    let mut server = Server::default();
+
+    while let Some(message) = read_message(&mut input) {
+        match Json::parse(&message) {
+            Ok(message) => server.handle(&message),
+            Err(error) => eprintln!("Ignoring malformed message: {}", error),
+        }
+    }
+}
+
+// Messages are framed by headers, of which only `Content-Length` matters.
+fn read_message(input: &mut impl BufRead) -> Option<String> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let mut body = vec![0; length?];
+    input.read_exact(&mut body).ok()?;
+    String::from_utf8(body).ok()
+}
+
+fn send(message: Json) {
+    let body = message.to_string();
+    let mut output = io::stdout().lock();
+    let _ = write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+    let _ = output.flush();
+}
+
+fn notify(method: &str, params: Json) {
+    send(Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("method", method.into()),
+        ("params", params),
+    ]));
+}
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<String, Document>,
+    shutting_down: bool,
+}
+
+impl Server {
+    fn handle(&mut self, message: &Json) {
+        let Some(method) = message.get("method").and_then(Json::as_str) else {
+            // A response to a request of ours; the server makes none.
+            return;
+        };
+        let params = message.get("params").unwrap_or(&Json::Null);
+
+        let Some(id) = message.get("id").cloned() else {
+            self.notification(method, params);
+            return;
+        };
+
+        let result = if self.shutting_down && method != "shutdown" {
+            Err((INVALID_REQUEST, "The server is shutting down.".to_string()))
+        } else {
+            self.request(method, params)
+        };
+        let response = match result {
+            Ok(result) => Json::object([("jsonrpc", "2.0".into()), ("id", id), ("result", result)]),
+            Err((code, message)) => Json::object([
+                ("jsonrpc", "2.0".into()),
+                ("id", id),
+                (
+                    "error",
+                    Json::object([("code", Json::Number(code)), ("message", message.into())]),
+                ),
+            ]),
+        };
+        send(response);
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) {
+        let uri = params
+            .get("textDocument")
+            .and_then(|document| document.get("uri"))
+            .and_then(Json::as_str)
+            .map(str::to_string);
+
+        match (method, uri) {
+            ("exit", _) => process::exit(if self.shutting_down { 0 } else { 1 }),
+            ("textDocument/didOpen", Some(uri)) => {
+                let text = params
+                    .get("textDocument")
+                    .and_then(|document| document.get("text"))
+                    .and_then(Json::as_str)
+                    .unwrap_or_default();
+                self.open(uri, text.to_string());
+            }
+            ("textDocument/didChange", Some(uri)) => {
+                let text = params
+                    .get("contentChanges")
+                    .and_then(Json::as_array)
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.get("text"))
+                    .and_then(Json::as_str);
+                if let Some(text) = text {
+                    self.open(uri, text.to_string());
+                }
+            }
+            ("textDocument/didClose", Some(uri)) => {
+                self.documents.remove(&uri);
+                publish(&uri, Vec::new());
+            }
+            _ => {}
+        }
+    }
+
+    fn open(&mut self, uri: String, text: String) {
+        let document = Document::new(text);
+        publish(&uri, document.diagnostics());
+        self.documents.insert(uri, document);
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> Result<Json, (f64, String)> {
+        match method {
+            "initialize" => return Ok(capabilities()),
+            "shutdown" => {
+                self.shutting_down = true;
+                return Ok(Json::Null);
+            }
+            _ => {}
+        }
+
+        let uri = params
+            .get("textDocument")
+            .and_then(|document| document.get("uri"))
+            .and_then(Json::as_str)
+            .unwrap_or_default();
+        let document = self.documents.get(uri);
+        let offset = document.and_then(|document| {
+            params
+                .get("position")
+                .and_then(|position| document.offset(position))
+        });
+
+        let result = match (method, document) {
+            ("textDocument/definition", Some(document)) => offset
+                .and_then(|offset| document.analysis.definition_at(offset))
+                .map(|definition| {
+                    document.location(uri, document.analysis.definitions[definition].span)
+                })
+                .into(),
+            ("textDocument/references", Some(document)) => {
+                let declaration = params
+                    .get("context")
+                    .and_then(|context| context.get("includeDeclaration"))
+                    .and_then(Json::as_bool)
+                    .unwrap_or(true);
+                offset
+                    .and_then(|offset| document.analysis.definition_at(offset))
+                    .map(|definition| {
+                        let declared = document.analysis.definitions[definition].span;
+                        document
+                            .analysis
+                            .references_to(definition)
+                            .filter(|span| declaration || *span != declared)
+                            .map(|span| document.location(uri, span))
+                            .collect::<Vec<Json>>()
+                    })
+                    .unwrap_or_default()
+                    .into()
+            }
+            ("textDocument/hover", Some(document)) => {
+                offset.and_then(|offset| document.hover(offset)).into()
+            }
+            ("textDocument/documentSymbol", Some(document)) => document.symbols().into(),
+            ("textDocument/completion", Some(document)) => {
+                document.completions(offset.unwrap_or(0)).into()
+            }
+            (
+                "textDocument/definition"
+                | "textDocument/references"
+                | "textDocument/hover"
+                | "textDocument/documentSymbol"
+                | "textDocument/completion",
+                None,
+            ) => Json::Null,
+            _ => {
+                return Err((METHOD_NOT_FOUND, format!("Unknown method '{}'.", method)));
+            }
+        };
+        Ok(result)
+    }
+}
+
+fn capabilities() -> Json {
+    Json::object([
+        (
+            "capabilities",
+            Json::object([
+                // Full sync: every change sends the whole document.
+                ("textDocumentSync", 1.into()),
+                ("definitionProvider", true.into()),
+                ("referencesProvider", true.into()),
+                ("hoverProvider", true.into()),
+                ("documentSymbolProvider", true.into()),
+                ("completionProvider", Json::object([])),
+            ]),
+        ),
+        (
+            "serverInfo",
+            Json::object([
+                ("name", "lox".into()),
+                ("version", env!("CARGO_PKG_VERSION").into()),
+            ]),
+        ),
+    ])
+}
+
+fn publish(uri: &str, diagnostics: Vec<Json>) {
+    notify(
+        "textDocument/publishDiagnostics",
+        Json::object([("uri", uri.into()), ("diagnostics", diagnostics.into())]),
+    );
+}
+
+struct Document {
+    text: String,
+    /// The byte offset each line starts at.
+    lines: Vec<usize>,
+    /// Errors from scanning, parsing and resolving, by line, with the span
+    /// of the offending text where the stage knows it.
+    errors: Vec<(usize, Option<Span>, String)>,
+    analysis: Analysis,
+}
+
+impl Document {
+    fn new(text: String) -> Document {
+        let lines = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        // A bug in any stage shouldn't take the whole server down with it;
+        // the document just goes without errors and names until it changes.
+        let checked = panic::catch_unwind(AssertUnwindSafe(|| check(&text)));
+        let (errors, analysis) = checked.unwrap_or_default();
+
+        Document {
+            text,
+            lines,
+            errors,
+            analysis,
+        }
+    }
+
+    fn diagnostics(&self) -> Vec<Json> {
+        self.errors
+            .iter()
+            .map(|(line, span, message)| {
+                let span = match (*line, *span) {
+                    (_, Some(span)) => span,
+                    // Errors at the end of the input are reported on line 0,
+                    // and belong after the last thing written.
+                    (0, None) => {
+                        let end = self.text.trim_end().len();
+                        Span { start: end, end }
+                    }
+                    (line, None) => self.anchor(line, message),
+                };
+                Json::object([
+                    ("range", self.range(span)),
+                    ("severity", 1.into()),
+                    ("source", "lox".into()),
+                    ("message", message.as_str().into()),
+                ])
+            })
+            .collect()
+    }
+
+    // The resolver's errors carry only a line, but name what they're about in
+    // quotes at the end, so they're anchored at its first mention there.
+    // Failing that, they cover the line's text without its indentation.
+    fn anchor(&self, line: usize, message: &str) -> Span {
+        let start = self.lines[line.min(self.lines.len()) - 1];
+        let end = self.text[start..]
+            .find('\n')
+            .map_or(self.text.len(), |end| start + end);
+
+        let named = message.rsplit('\'').nth(1).and_then(|name| {
+            scan(&self.text, 1)
+                .0
+                .into_iter()
+                .find(|lexeme| {
+                    lexeme.line_number == line
+                        && matches!(&lexeme.token, Token::Identifier(found) if found == name)
+                })
+                .map(|lexeme| lexeme.span)
+        });
+        named.unwrap_or_else(|| {
+            let text = &self.text[start..end];
+            let indent = text.len() - text.trim_start().len();
+            Span {
+                start: start + indent,
+                end: start + text.trim_end().len(),
+            }
+        })
+    }
+
+    // Positions count lines from 0 and characters in UTF-16 code units.
+    fn position(&self, offset: usize) -> Json {
+        let line = self.lines.partition_point(|&start| start <= offset) - 1;
+        let character = self.text[self.lines[line]..offset].encode_utf16().count();
+        Json::object([("line", line.into()), ("character", character.into())])
+    }
+
+    fn offset(&self, position: &Json) -> Option<usize> {
+        let line = position.get("line")?.as_usize()?;
+        let character = position.get("character")?.as_usize()?;
+        let Some(&start) = self.lines.get(line) else {
+            return Some(self.text.len());
+        };
+
+        let mut units = 0;
+        for (i, c) in self.text[start..].char_indices() {
+            if units >= character || c == '\n' {
+                return Some(start + i);
+            }
+            units += c.len_utf16();
+        }
+        Some(self.text.len())
+    }
+
+    fn range(&self, span: Span) -> Json {
+        Json::object([
+            ("start", self.position(span.start)),
+            ("end", self.position(span.end)),
+        ])
+    }
+
+    fn location(&self, uri: &str, span: Span) -> Json {
+        Json::object([("uri", uri.into()), ("range", self.range(span))])
+    }
+
+    fn hover(&self, offset: usize) -> Option<Json> {
+        let (contents, span) = match self.analysis.definition_at(offset) {
+            Some(definition) => {
+                let reference = self
+                    .analysis
+                    .references
+                    .iter()
+                    .find(|(span, _)| span.start <= offset && offset <= span.end)
+                    .map(|(span, _)| *span)?;
+                (describe(&self.analysis.definitions[definition]), reference)
+            }
+            None => {
+                let (name, span) = self.identifier_at(offset)?;
+                let native = natives::lookup(&name)?;
+                let contents = format!(
+                    "```lox\nfun {}\n```\n\nBuilt-in function taking {} argument(s).",
+                    name, native.arity
+                );
+                (contents, span)
+            }
+        };
+
+        Some(Json::object([
+            (
+                "contents",
+                Json::object([("kind", "markdown".into()), ("value", contents.into())]),
+            ),
+            ("range", self.range(span)),
+        ]))
+    }
+
+    fn identifier_at(&self, offset: usize) -> Option<(String, Span)> {
+        scan(&self.text, 1)
+            .0
+            .into_iter()
+            .find(|lexeme| lexeme.span.start <= offset && offset <= lexeme.span.end)
+            .and_then(|lexeme| match lexeme.token {
+                Token::Identifier(name) => Some((name, lexeme.span)),
+                _ => None,
+            })
+    }
+
+    // Globals, and functions at any depth, each with the statement that
+    // declares it as its range.
+    fn symbols(&self) -> Vec<Json> {
+        let tree = cst::parse(&self.text);
+
+        self.analysis
+            .definitions
+            .iter()
+            .filter(|definition| {
+                definition.kind != Kind::Parameter
+                    && (definition.global || definition.kind == Kind::Function)
+            })
+            .map(|definition| {
+                let statement = statement_around(&tree, definition.span).unwrap_or(definition.span);
+                Json::object([
+                    ("name", definition.name.as_str().into()),
+                    ("detail", signature(definition).into()),
+                    ("kind", symbol_kind(definition).into()),
+                    ("range", self.range(statement)),
+                    ("selectionRange", self.range(definition.span)),
+                ])
+            })
+            .collect()
+    }
+
+    fn completions(&self, offset: usize) -> Vec<Json> {
+        let names = self
+            .analysis
+            .visible_at(offset)
+            .into_iter()
+            .map(|definition| {
+                let kind = match definition.kind {
+                    Kind::Function => 3,
+                    Kind::Constant => 21,
+                    Kind::Variable | Kind::Parameter if definition.params.is_some() => 3,
+                    Kind::Variable | Kind::Parameter => 6,
+                };
+                Json::object([
+                    ("label", definition.name.as_str().into()),
+                    ("kind", kind.into()),
+                    ("detail", signature(definition).into()),
+                ])
+            });
+
+        let natives = natives::names().into_iter().map(|name| {
+            let kind = if natives::lookup(name).is_some() {
+                3
+            } else {
+                21
+            };
+            Json::object([("label", name.into()), ("kind", kind.into())])
+        });
+
+        let mut keywords: Vec<&str> = KEYWORD_MAP.keys().copied().collect();
+        keywords.sort();
+        let keywords = keywords
+            .into_iter()
+            .map(|keyword| Json::object([("label", keyword.into()), ("kind", 14.into())]));
+
+        names.chain(natives).chain(keywords).collect()
+    }
+}
+
+type Errors = Vec<(usize, Option<Span>, String)>;
+
+fn check(text: &str) -> (Errors, Analysis) {
+    let mut errors: Errors = scan_spanned(text, 1)
+        .1
+        .into_iter()
+        .filter_map(|error| {
+            let message = match error.token {
+                Token::Error(c, _) => format!("Unexpected character: {}", c),
+                Token::ErrorString(..) => "Unterminated string.".to_string(),
+                Token::LexError(message, _) => message,
+                _ => return None,
+            };
+            Some((error.line_number, Some(error.span), message))
+        })
+        .collect();
+
+    let (statements, parse_errors) = parse_source(text);
+    let stage_errors = if parse_errors.is_empty() {
+        resolve(&statements)
+    } else {
+        parse_errors
+    };
+    errors.extend(stage_errors.into_iter().map(|error| match error {
+        Error::ParseError(line, message, span) => (line, span, message),
+        Error::CompileError(line, message) | Error::RuntimeError(line, message) => {
+            (line, None, message)
+        }
+        Error::Thrown(value, line) => (line, None, value.to_string()),
+    }));
+
+    (errors, scope::analyze(&statements, text))
+}
+
+fn signature(definition: &Definition) -> String {
+    let params = definition.params.as_ref().map(|params| params.join(", "));
+    match (definition.kind, params) {
+        (Kind::Function, params) => {
+            format!("fun {}({})", definition.name, params.unwrap_or_default())
+        }
+        (Kind::Variable, Some(params)) => format!("var {} = fun ({})", definition.name, params),
+        (Kind::Constant, Some(params)) => format!("const {} = fun ({})", definition.name, params),
+        (Kind::Variable, None) => format!("var {}", definition.name),
+        (Kind::Constant, None) => format!("const {}", definition.name),
+        (Kind::Parameter, _) => format!("(parameter) {}", definition.name),
+    }
+}
+
+fn describe(definition: &Definition) -> String {
+    let mut description = format!("```lox\n{}\n```", signature(definition));
+    if let Some(doc) = &definition.doc {
+        description.push_str("\n\n");
+        description.push_str(doc);
+    }
+    description
+}
+
+// Symbol kinds from the LSP specification.
+fn symbol_kind(definition: &Definition) -> usize {
+    match definition.kind {
+        Kind::Function => 12,
+        Kind::Constant => 14,
+        Kind::Variable | Kind::Parameter => 13,
+    }
+}
+
+// The innermost statement that contains `span`.
+fn statement_around(node: &SyntaxNode, span: Span) -> Option<Span> {
+    node.children.iter().find_map(|child| match child {
+        SyntaxElement::Node(child) => {
+            let around = child.span();
+            if around.start > span.start || span.end > around.end {
+                return None;
+            }
+            let inner = statement_around(child, span);
+            match child.kind {
+                NodeKind::Statement => inner.or(Some(around)),
+                _ => inner,
+            }
+        }
+        SyntaxElement::Token(_) => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each diagnostic's range, as (line, character) pairs.
+    fn ranges(text: &str) -> Vec<((usize, usize), (usize, usize))> {
+        let point = |position: &Json| {
+            (
+                position.get("line").and_then(Json::as_usize).unwrap(),
+                position.get("character").and_then(Json::as_usize).unwrap(),
+            )
+        };
+        Document::new(text.to_string())
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| {
+                let range = diagnostic.get("range").unwrap();
+                (
+                    point(range.get("start").unwrap()),
+                    point(range.get("end").unwrap()),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn positions_and_offsets_round_trip_in_utf16() {
+        let document = Document::new("var s = \"é😀\";\nprint s;\n".to_string());
+        for offset in (0..=document.text.len()).filter(|&i| document.text.is_char_boundary(i)) {
+            let position = document.position(offset);
+            assert_eq!(document.offset(&position), Some(offset));
+        }
+
+        // `é` is one UTF-16 unit and `😀` two.
+        let after = document.text.find('"').unwrap() + "\"é😀".len();
+        let position = document.position(after);
+        assert_eq!(position.get("character").and_then(Json::as_usize), Some(12));
+    }
+
+    #[test]
+    fn offsets_past_a_line_end_stop_at_it() {
+        let document = Document::new("ab\ncd".to_string());
+        let position = Json::object([("line", 0.into()), ("character", 10.into())]);
+        assert_eq!(document.offset(&position), Some(2));
+        let position = Json::object([("line", 5.into()), ("character", 0.into())]);
+        assert_eq!(document.offset(&position), Some(5));
+    }
+
+    #[test]
+    fn parse_errors_cover_the_offending_token() {
+        assert_eq!(ranges("var a = );\n"), [((0, 8), (0, 9))]);
+        assert_eq!(ranges("print \"é\" 1;\n"), [((0, 10), (0, 11))]);
+    }
+
+    #[test]
+    fn scan_errors_cover_the_rejected_text() {
+        assert_eq!(ranges("var a = 1;\nvar b = @;\n")[0], ((1, 8), (1, 9)));
+    }
+
+    #[test]
+    fn end_of_input_errors_sit_at_the_end_of_the_last_written_line() {
+        assert_eq!(ranges("var a = 1;\nprint a\n\n"), [((1, 7), (1, 7))]);
+        assert_eq!(ranges("print \"${1 +}\";\n"), [((0, 6), (0, 14))]);
+    }
+
+    #[test]
+    fn resolver_errors_cover_the_name_they_are_about() {
+        assert_eq!(ranges("const c = 1;\n  c = 2;\n"), [((1, 2), (1, 3))]);
+    }
+}
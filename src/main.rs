@@ -1,16 +1,29 @@
 use std::{env, process};
 
+use crate::bench::bench;
 use crate::enums::environment::Environment;
 use crate::evaluator::evaluate;
 use crate::parser::parse;
 use crate::run::run;
 use crate::tokenizer::tokenize;
-use crate::utils::{if_error_exit, print};
+use crate::utils::{flag_value, get_file_contents, if_error_exit, print, split_arguments};
 
+mod bench;
+mod cst;
 pub mod enums;
 mod evaluator;
+mod formatter;
+mod gc;
+mod highlight;
+mod import;
+mod json;
+mod linter;
+mod lsp;
+mod natives;
 mod parser;
+mod resolver;
 mod run;
+mod scope;
 mod tokenizer;
 mod utils;
 
@@ -19,17 +32,39 @@ mod utils;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
+    // The language server talks over stdio and takes no file.
+    if args.get(1).is_some_and(|command| command == "lsp") {
+        lsp::serve();
+        return;
+    }
     if args.len() < 3 {
         eprintln!("Usage: {} tokenize <filename>", args[0]);
+        process::exit(64);
     }
 
     let command = &args[1];
-    let filename = &args[2];
+    let (filename, flags) = split_arguments(&args[2..]).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(64);
+    });
+
+    if flags.iter().any(|flag| flag == "--gc-stress") {
+        gc::set_stress(true);
+    }
+
+    if let Some(modules) = flag_value(&flags, "--disable-natives") {
+        for module in modules.split(',') {
+            if !natives::set_enabled(module, false) {
+                eprintln!("Unknown native module: {}", module);
+                process::exit(64);
+            }
+        }
+    }
 
     match command.as_str() {
         "tokenize" => {
             eprintln!("Logs from your program will appear here!");
-            let (tokens, errors) = tokenize(filename);
+            let (tokens, errors) = tokenize(&filename);
             let has_errors = !errors.is_empty();
 
             for x in errors {
@@ -41,16 +76,16 @@ fn main() {
             if_error_exit(has_errors, 65);
         }
         "parse" => {
-            let (_, errors) = tokenize(filename);
+            let (_, errors) = tokenize(&filename);
             if_error_exit(!errors.is_empty(), 65);
 
-            let (expressions, errors) = parse(filename);
+            let (expressions, errors) = parse(&filename);
             if_error_exit(!errors.is_empty(), 65);
 
             print(expressions)
         }
         "evaluate" => {
-            let (expressions, errors) = parse(filename);
+            let (expressions, errors) = parse(&filename);
             let mut env = Environment::new();
             if_error_exit(!errors.is_empty(), 70);
 
@@ -63,9 +98,18 @@ fn main() {
                 }
             }
         }
-        "run" => run(filename),
+        "run" => run(&filename),
+        "cst" => print!("{}", cst::parse(&get_file_contents(&filename)).outline()),
+        "fmt" => formatter::fmt(&filename, &flags),
+        "lint" => linter::lint(&filename, &flags),
+        "highlight" => highlight::highlight(&filename, &flags),
+        "bench" => bench(&filename, &flags),
         _ => {
             eprintln!("Unknown command: {}", command);
         }
     }
+
+    if flags.iter().any(|flag| flag == "--gc-stats") {
+        eprintln!("{}", gc::stats());
+    }
 }
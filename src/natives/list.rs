@@ -0,0 +1,129 @@
+use crate::enums::error::Error;
+use crate::enums::list::List;
+use crate::evaluator::{Value, call, equal, truthy};
+use crate::natives::Arity::Fixed;
+use crate::natives::Native;
+
+pub const NATIVES: &[Native] = &[
+    Native {
+        name: "push",
+        arity: Fixed(2),
+        function: push,
+    },
+    Native {
+        name: "pop",
+        arity: Fixed(1),
+        function: pop,
+    },
+    Native {
+        name: "insert",
+        arity: Fixed(3),
+        function: insert,
+    },
+    Native {
+        name: "remove",
+        arity: Fixed(2),
+        function: remove,
+    },
+    Native {
+        name: "slice",
+        arity: Fixed(3),
+        function: slice,
+    },
+    Native {
+        name: "contains",
+        arity: Fixed(2),
+        function: contains,
+    },
+    Native {
+        name: "map",
+        arity: Fixed(2),
+        function: map,
+    },
+    Native {
+        name: "filter",
+        arity: Fixed(2),
+        function: filter,
+    },
+];
+
+fn list<'a>(value: &'a Value, name: &str, line: usize) -> Result<&'a List, Error> {
+    match value {
+        Value::List(list) => Ok(list),
+        _ => Err(Error::RuntimeError(
+            line,
+            format!("First argument to '{}' must be a list.", name),
+        )),
+    }
+}
+
+fn push(args: &[Value], line: usize) -> Result<Value, Error> {
+    let list = list(&args[0], "push", line)?;
+    list.borrow_mut().push(args[1].clone());
+    Ok(Value::Nil)
+}
+
+fn pop(args: &[Value], line: usize) -> Result<Value, Error> {
+    let list = list(&args[0], "pop", line)?;
+    let value = list.borrow_mut().pop();
+    value.ok_or_else(|| Error::RuntimeError(line, "Can't pop from an empty list.".to_string()))
+}
+
+fn insert(args: &[Value], line: usize) -> Result<Value, Error> {
+    let list = list(&args[0], "insert", line)?;
+    let len = list.borrow().len();
+    // Inserting at the end is allowed, so the bound is one past the last index.
+    let index = List::position(&args[1], len + 1, line)?;
+    list.borrow_mut().insert(index, args[2].clone());
+    Ok(Value::Nil)
+}
+
+fn remove(args: &[Value], line: usize) -> Result<Value, Error> {
+    let list = list(&args[0], "remove", line)?;
+    let len = list.borrow().len();
+    let index = List::position(&args[1], len, line)?;
+    let value = list.borrow_mut().remove(index);
+    Ok(value)
+}
+
+fn slice(args: &[Value], line: usize) -> Result<Value, Error> {
+    let list = list(&args[0], "slice", line)?;
+    let values = list.borrow();
+    let start = List::position(&args[1], values.len() + 1, line)?;
+    let end = List::position(&args[2], values.len() + 1, line)?;
+
+    if start > end {
+        let error = format!("Slice start {} is after its end {}.", start, end);
+        return Err(Error::RuntimeError(line, error));
+    }
+
+    Ok(Value::List(List::new(values[start..end].to_vec())))
+}
+
+fn contains(args: &[Value], line: usize) -> Result<Value, Error> {
+    let list = list(&args[0], "contains", line)?;
+    let found = list.borrow().iter().any(|value| equal(value, &args[1]));
+    Ok(Value::Boolean(found))
+}
+
+// The callbacks below see a copy of the list, so one that pushes to or pops
+// from the list it's given doesn't change what gets visited.
+fn map(args: &[Value], line: usize) -> Result<Value, Error> {
+    let values = list(&args[0], "map", line)?.borrow().clone();
+    let mapped = values
+        .into_iter()
+        .map(|value| call(&args[1], vec![value], line))
+        .collect::<Result<Vec<Value>, Error>>()?;
+    Ok(Value::List(List::new(mapped)))
+}
+
+fn filter(args: &[Value], line: usize) -> Result<Value, Error> {
+    let values = list(&args[0], "filter", line)?.borrow().clone();
+    let mut kept = Vec::new();
+    for value in values {
+        if truthy(call(&args[1], vec![value.clone()], line)?) {
+            kept.push(value);
+        }
+    }
+    Ok(Value::List(List::new(kept)))
+}
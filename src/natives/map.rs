@@ -0,0 +1,75 @@
+use crate::enums::error::Error;
+use crate::enums::list::List;
+use crate::enums::map::{Key, Map};
+use crate::evaluator::Value;
+use crate::natives::Arity::Fixed;
+use crate::natives::Native;
+
+pub const NATIVES: &[Native] = &[
+    Native {
+        name: "keys",
+        arity: Fixed(1),
+        function: keys,
+    },
+    Native {
+        name: "values",
+        arity: Fixed(1),
+        function: values,
+    },
+    Native {
+        name: "has",
+        arity: Fixed(2),
+        function: has,
+    },
+    Native {
+        name: "delete",
+        arity: Fixed(2),
+        function: delete,
+    },
+];
+
+fn map<'a>(value: &'a Value, name: &str, line: usize) -> Result<&'a Map, Error> {
+    match value {
+        Value::Map(map) => Ok(map),
+        _ => Err(Error::RuntimeError(
+            line,
+            format!("First argument to '{}' must be a map.", name),
+        )),
+    }
+}
+
+fn keys(args: &[Value], line: usize) -> Result<Value, Error> {
+    let map = map(&args[0], "keys", line)?;
+    let keys = map.borrow().iter().map(|(key, _)| key.to_value()).collect();
+    Ok(Value::List(List::new(keys)))
+}
+
+fn values(args: &[Value], line: usize) -> Result<Value, Error> {
+    let map = map(&args[0], "values", line)?;
+    let values = map
+        .borrow()
+        .iter()
+        .map(|(_, value)| value.clone())
+        .collect();
+    Ok(Value::List(List::new(values)))
+}
+
+fn has(args: &[Value], line: usize) -> Result<Value, Error> {
+    let map = map(&args[0], "has", line)?;
+    let key = Key::new(&args[1], line)?;
+    Ok(Value::Boolean(map.borrow().get(&key).is_some()))
+}
+
+/// Removes a key, returning its value, or nil if it wasn't there.
+fn delete(args: &[Value], line: usize) -> Result<Value, Error> {
+    let map = map(&args[0], "delete", line)?;
+    let key = Key::new(&args[1], line)?;
+    if let Some(kind) = map.borrow().fixed(&key) {
+        return Err(Error::RuntimeError(
+            line,
+            format!("Can't delete {} '{}'.", kind, args[1]),
+        ));
+    }
+    let value = map.borrow_mut().remove(&key);
+    Ok(value.unwrap_or(Value::Nil))
+}
@@ -0,0 +1,218 @@
+use std::f64::consts;
+
+use crate::enums::error::Error;
+use crate::enums::lox_string::LoxString;
+use crate::evaluator::Value;
+use crate::natives::Arity::{AtLeast, Fixed};
+use crate::natives::Native;
+
+pub const CONSTANTS: &[(&str, f64)] = &[("PI", consts::PI), ("E", consts::E)];
+
+pub const NATIVES: &[Native] = &[
+    Native {
+        name: "sqrt",
+        arity: Fixed(1),
+        function: sqrt,
+    },
+    Native {
+        name: "pow",
+        arity: Fixed(2),
+        function: pow,
+    },
+    Native {
+        name: "abs",
+        arity: Fixed(1),
+        function: abs,
+    },
+    Native {
+        name: "floor",
+        arity: Fixed(1),
+        function: floor,
+    },
+    Native {
+        name: "ceil",
+        arity: Fixed(1),
+        function: ceil,
+    },
+    Native {
+        name: "round",
+        arity: Fixed(1),
+        function: round,
+    },
+    Native {
+        name: "min",
+        arity: AtLeast(1),
+        function: min,
+    },
+    Native {
+        name: "max",
+        arity: AtLeast(1),
+        function: max,
+    },
+    Native {
+        name: "sin",
+        arity: Fixed(1),
+        function: sin,
+    },
+    Native {
+        name: "cos",
+        arity: Fixed(1),
+        function: cos,
+    },
+    Native {
+        name: "tan",
+        arity: Fixed(1),
+        function: tan,
+    },
+    Native {
+        name: "log",
+        arity: Fixed(1),
+        function: log,
+    },
+    Native {
+        name: "exp",
+        arity: Fixed(1),
+        function: exp,
+    },
+    Native {
+        name: "is_nan",
+        arity: Fixed(1),
+        function: is_nan,
+    },
+    Native {
+        name: "is_finite",
+        arity: Fixed(1),
+        function: is_finite,
+    },
+    Native {
+        name: "parse_number",
+        arity: Fixed(1),
+        function: parse_number,
+    },
+    Native {
+        name: "to_string",
+        arity: Fixed(1),
+        function: to_string,
+    },
+];
+
+fn number(value: &Value, name: &str, line: usize) -> Result<f64, Error> {
+    match value {
+        Value::Number(number) => Ok(*number),
+        _ => Err(Error::RuntimeError(
+            line,
+            format!("Arguments to '{}' must be numbers, got {}.", name, value),
+        )),
+    }
+}
+
+fn unary(args: &[Value], name: &str, line: usize, f: fn(f64) -> f64) -> Result<Value, Error> {
+    Ok(Value::Number(f(number(&args[0], name, line)?)))
+}
+
+fn sqrt(args: &[Value], line: usize) -> Result<Value, Error> {
+    unary(args, "sqrt", line, f64::sqrt)
+}
+
+fn pow(args: &[Value], line: usize) -> Result<Value, Error> {
+    let base = number(&args[0], "pow", line)?;
+    let exponent = number(&args[1], "pow", line)?;
+    Ok(Value::Number(base.powf(exponent)))
+}
+
+fn abs(args: &[Value], line: usize) -> Result<Value, Error> {
+    unary(args, "abs", line, f64::abs)
+}
+
+fn floor(args: &[Value], line: usize) -> Result<Value, Error> {
+    unary(args, "floor", line, f64::floor)
+}
+
+fn ceil(args: &[Value], line: usize) -> Result<Value, Error> {
+    unary(args, "ceil", line, f64::ceil)
+}
+
+/// Rounds half away from zero.
+fn round(args: &[Value], line: usize) -> Result<Value, Error> {
+    unary(args, "round", line, f64::round)
+}
+
+fn min(args: &[Value], line: usize) -> Result<Value, Error> {
+    let mut min = f64::INFINITY;
+    for arg in args {
+        min = min.min(number(arg, "min", line)?);
+    }
+    Ok(Value::Number(min))
+}
+
+fn max(args: &[Value], line: usize) -> Result<Value, Error> {
+    let mut max = f64::NEG_INFINITY;
+    for arg in args {
+        max = max.max(number(arg, "max", line)?);
+    }
+    Ok(Value::Number(max))
+}
+
+fn sin(args: &[Value], line: usize) -> Result<Value, Error> {
+    unary(args, "sin", line, f64::sin)
+}
+
+fn cos(args: &[Value], line: usize) -> Result<Value, Error> {
+    unary(args, "cos", line, f64::cos)
+}
+
+fn tan(args: &[Value], line: usize) -> Result<Value, Error> {
+    unary(args, "tan", line, f64::tan)
+}
+
+/// The natural logarithm.
+fn log(args: &[Value], line: usize) -> Result<Value, Error> {
+    unary(args, "log", line, f64::ln)
+}
+
+fn exp(args: &[Value], line: usize) -> Result<Value, Error> {
+    unary(args, "exp", line, f64::exp)
+}
+
+fn is_nan(args: &[Value], line: usize) -> Result<Value, Error> {
+    Ok(Value::Boolean(number(&args[0], "is_nan", line)?.is_nan()))
+}
+
+fn is_finite(args: &[Value], line: usize) -> Result<Value, Error> {
+    Ok(Value::Boolean(
+        number(&args[0], "is_finite", line)?.is_finite(),
+    ))
+}
+
+/// Parses a number the way the scanner reads number literals, allowing
+/// surrounding whitespace and a leading minus. Returns nil if it isn't one.
+fn parse_number(args: &[Value], line: usize) -> Result<Value, Error> {
+    let Value::String(string) = &args[0] else {
+        return Err(Error::RuntimeError(
+            line,
+            format!(
+                "Argument to 'parse_number' must be a string, got {}.",
+                args[0]
+            ),
+        ));
+    };
+
+    let string = string.as_str();
+    let text = string.trim();
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    let literal = !digits.is_empty()
+        && digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && digits.matches('.').count() <= 1
+        && !digits.starts_with('.')
+        && !digits.ends_with('.');
+
+    match text.parse::<f64>() {
+        Ok(number) if literal => Ok(Value::Number(number)),
+        _ => Ok(Value::Nil),
+    }
+}
+
+/// Formats any value the way `print` does.
+fn to_string(args: &[Value], _: usize) -> Result<Value, Error> {
+    Ok(Value::String(LoxString::new(args[0].to_string())))
+}
@@ -0,0 +1,318 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use crate::enums::error::Error;
+use crate::enums::range::Range;
+use crate::evaluator::Value;
+use crate::natives::Arity::{Between, Fixed};
+
+mod list;
+mod map;
+mod math;
+pub mod string;
+
+pub type NativeFn = fn(&[Value], usize) -> Result<Value, Error>;
+
+pub enum Arity {
+    Fixed(usize),
+    Between(usize, usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    fn accepts(&self, count: usize) -> bool {
+        match self {
+            Arity::Fixed(arity) => count == *arity,
+            Arity::Between(min, max) => (*min..=*max).contains(&count),
+            Arity::AtLeast(min) => count >= *min,
+        }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arity::Fixed(arity) => write!(fmt, "{}", arity),
+            Arity::Between(min, max) => write!(fmt, "{} to {}", min, max),
+            Arity::AtLeast(min) => write!(fmt, "at least {}", min),
+        }
+    }
+}
+
+/// A function implemented in Rust. Natives are only reached when no Lox
+/// function or variable of the same name is in scope, and only while their
+/// module is enabled.
+pub struct Native {
+    pub name: &'static str,
+    pub arity: Arity,
+    pub function: NativeFn,
+}
+
+const CORE: &[Native] = &[
+    Native {
+        name: "clock",
+        arity: Fixed(0),
+        function: clock,
+    },
+    Native {
+        name: "len",
+        arity: Fixed(1),
+        function: len,
+    },
+    Native {
+        name: "range",
+        arity: Between(2, 3),
+        function: range,
+    },
+];
+
+/// A group of natives and constants that can be switched off as a whole, for
+/// embedders that want a smaller or sandboxed standard library.
+pub struct Module {
+    pub name: &'static str,
+    pub natives: &'static [Native],
+    pub constants: &'static [(&'static str, f64)],
+}
+
+impl Module {
+    // Whether values like `value` are this module's to handle.
+    fn owns(&self, value: &Value) -> bool {
+        matches!(
+            (self.name, value),
+            ("list", Value::List(_)) | ("map", Value::Map(_)) | ("string", Value::String(_))
+        )
+    }
+}
+
+const MODULES: &[Module] = &[
+    Module {
+        name: "core",
+        natives: CORE,
+        constants: &[],
+    },
+    Module {
+        name: "list",
+        natives: list::NATIVES,
+        constants: &[],
+    },
+    Module {
+        name: "map",
+        natives: map::NATIVES,
+        constants: &[],
+    },
+    Module {
+        name: "string",
+        natives: string::NATIVES,
+        constants: &[],
+    },
+    Module {
+        name: "math",
+        natives: math::NATIVES,
+        constants: math::CONSTANTS,
+    },
+];
+
+thread_local! {
+    static DISABLED: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Enables or disables a module by name. Returns false if there's no such
+/// module.
+pub fn set_enabled(name: &str, enabled: bool) -> bool {
+    let Some(module) = MODULES.iter().find(|module| module.name == name) else {
+        return false;
+    };
+
+    DISABLED.with(|disabled| {
+        let mut disabled = disabled.borrow_mut();
+        disabled.retain(|&disabled| disabled != module.name);
+        if !enabled {
+            disabled.push(module.name);
+        }
+    });
+    true
+}
+
+fn enabled_modules() -> impl Iterator<Item = &'static Module> {
+    let disabled = DISABLED.with(|disabled| disabled.borrow().clone());
+    MODULES
+        .iter()
+        .filter(move |module| !disabled.contains(&module.name))
+}
+
+pub fn lookup(name: &str) -> Option<&'static Native> {
+    enabled_modules()
+        .flat_map(|module| module.natives)
+        .find(|native| native.name == name)
+}
+
+/// The names of every enabled native and constant.
+pub fn names() -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = enabled_modules()
+        .flat_map(|module| {
+            let natives = module.natives.iter().map(|native| native.name);
+            natives.chain(module.constants.iter().map(|(name, _)| *name))
+        })
+        .collect();
+    // Natives shared between modules are listed once.
+    let mut seen = HashSet::new();
+    names.retain(|name| seen.insert(*name));
+    names
+}
+
+pub fn constant(name: &str) -> Option<Value> {
+    enabled_modules()
+        .flat_map(|module| module.constants)
+        .find(|(constant, _)| *constant == name)
+        .map(|(_, value)| Value::Number(*value))
+}
+
+pub fn call_native(native: &Native, args: &[Value], line: usize) -> Result<Value, Error> {
+    let native = overload(native, args);
+    if !native.arity.accepts(args.len()) {
+        let error = format!(
+            "Expected {} arguments but got {}.",
+            native.arity,
+            args.len()
+        );
+        return Err(Error::RuntimeError(line, error));
+    }
+
+    (native.function)(args, line)
+}
+
+// Natives of the same name in different modules, like `slice` for lists and
+// strings, are told apart by their first argument, so each module keeps its
+// own behaviour and takes it along when it's disabled.
+fn overload<'a>(native: &'a Native, args: &[Value]) -> &'a Native {
+    let Some(first) = args.first() else {
+        return native;
+    };
+    enabled_modules()
+        .filter(|module| module.owns(first))
+        .flat_map(|module| module.natives)
+        .find(|candidate| candidate.name == native.name)
+        .unwrap_or(native)
+}
+
+fn clock(_: &[Value], line: usize) -> Result<Value, Error> {
+    let now = SystemTime::now();
+    if let Ok(now) = now.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(Value::Number(now.as_secs_f64()))
+    } else {
+        Err(Error::RuntimeError(
+            line,
+            "Failed to get current time".to_string(),
+        ))
+    }
+}
+
+fn len(args: &[Value], line: usize) -> Result<Value, Error> {
+    let len = match &args[0] {
+        Value::List(list) => list.borrow().len(),
+        Value::Map(map) => map.borrow().len(),
+        Value::Range(range) => range.len(),
+        Value::String(string) => string.as_str().chars().count(),
+        _ => {
+            return Err(Error::RuntimeError(
+                line,
+                "Can only take the length of strings, lists, maps and ranges.".to_string(),
+            ));
+        }
+    };
+
+    Ok(Value::Number(len as f64))
+}
+
+fn range(args: &[Value], line: usize) -> Result<Value, Error> {
+    let mut bounds = [0.0, 0.0, 1.0];
+    for (bound, arg) in bounds.iter_mut().zip(args) {
+        match arg {
+            Value::Number(number) => *bound = *number,
+            _ => {
+                return Err(Error::RuntimeError(
+                    line,
+                    "Range bounds and step must be numbers.".to_string(),
+                ));
+            }
+        }
+    }
+
+    let [start, end, step] = bounds;
+    if step == 0.0 || step.is_nan() {
+        return Err(Error::RuntimeError(
+            line,
+            "Range step can't be zero.".to_string(),
+        ));
+    }
+
+    Ok(Value::Range(Rc::new(Range { start, end, step })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::list::List;
+    use crate::enums::lox_string::LoxString;
+
+    fn call(name: &str, args: &[Value]) -> Result<Value, Error> {
+        let native = lookup(name).unwrap_or_else(|| panic!("no native '{}'", name));
+        call_native(native, args, 1)
+    }
+
+    fn string(string: &str) -> Value {
+        Value::String(LoxString::from(string))
+    }
+
+    fn list(values: &[f64]) -> Value {
+        Value::List(List::new(
+            values.iter().map(|&n| Value::Number(n)).collect(),
+        ))
+    }
+
+    fn message(result: Result<Value, Error>) -> String {
+        match result {
+            Err(Error::RuntimeError(_, message)) => message,
+            other => panic!("expected a runtime error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn shared_natives_go_to_the_module_that_owns_the_argument() {
+        let (one, three) = (Value::Number(1.0), Value::Number(3.0));
+        let slice = [string("héllo"), one.clone(), three.clone()];
+        assert_eq!(call("slice", &slice).unwrap().to_string(), "él");
+        let slice = [list(&[1.0, 2.0, 3.0, 4.0]), one.clone(), three];
+        assert_eq!(call("slice", &slice).unwrap().to_string(), "[2, 3]");
+
+        let contains = [string("héllo"), string("él")];
+        assert_eq!(call("contains", &contains).unwrap(), Value::Boolean(true));
+        let contains = [list(&[1.0, 2.0]), one];
+        assert_eq!(call("contains", &contains).unwrap(), Value::Boolean(true));
+    }
+
+    #[test]
+    fn disabling_a_module_takes_its_side_of_shared_natives() {
+        let strings = [string("abc"), string("b")];
+        let lists = [list(&[1.0]), Value::Number(1.0)];
+
+        set_enabled("string", false);
+        assert_eq!(
+            message(call("contains", &strings)),
+            "First argument to 'contains' must be a list."
+        );
+        assert_eq!(call("contains", &lists).unwrap(), Value::Boolean(true));
+
+        set_enabled("string", true);
+        set_enabled("list", false);
+        assert_eq!(call("contains", &strings).unwrap(), Value::Boolean(true));
+        assert_eq!(
+            message(call("contains", &lists)),
+            "First argument to 'contains' must be a string."
+        );
+        assert_eq!(names().iter().filter(|&&name| name == "slice").count(), 1);
+    }
+}
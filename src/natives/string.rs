@@ -0,0 +1,249 @@
+use crate::enums::error::Error;
+use crate::enums::list::List;
+use crate::enums::lox_string::LoxString;
+use crate::evaluator::Value;
+use crate::natives::Arity::Fixed;
+use crate::natives::Native;
+
+// Every index and length here counts characters, not bytes.
+
+// The longest string `repeat` will build, in bytes.
+const MAX_REPEAT_LEN: usize = 1 << 30;
+
+pub const NATIVES: &[Native] = &[
+    Native {
+        name: "substring",
+        arity: Fixed(3),
+        function: substring,
+    },
+    Native {
+        name: "slice",
+        arity: Fixed(3),
+        function: slice,
+    },
+    Native {
+        name: "contains",
+        arity: Fixed(2),
+        function: contains,
+    },
+    Native {
+        name: "index_of",
+        arity: Fixed(2),
+        function: index_of,
+    },
+    Native {
+        name: "starts_with",
+        arity: Fixed(2),
+        function: starts_with,
+    },
+    Native {
+        name: "ends_with",
+        arity: Fixed(2),
+        function: ends_with,
+    },
+    Native {
+        name: "upper",
+        arity: Fixed(1),
+        function: upper,
+    },
+    Native {
+        name: "lower",
+        arity: Fixed(1),
+        function: lower,
+    },
+    Native {
+        name: "trim",
+        arity: Fixed(1),
+        function: trim,
+    },
+    Native {
+        name: "split",
+        arity: Fixed(2),
+        function: split,
+    },
+    Native {
+        name: "replace",
+        arity: Fixed(3),
+        function: replace,
+    },
+    Native {
+        name: "repeat",
+        arity: Fixed(2),
+        function: repeat,
+    },
+];
+
+fn string(value: &Value, name: &str, position: &str, line: usize) -> Result<String, Error> {
+    match value {
+        Value::String(string) => Ok(string.to_string()),
+        _ => Err(Error::RuntimeError(
+            line,
+            format!("{} argument to '{}' must be a string.", position, name),
+        )),
+    }
+}
+
+fn new_string(string: String) -> Value {
+    Value::String(LoxString::new(string))
+}
+
+fn substring(args: &[Value], line: usize) -> Result<Value, Error> {
+    characters(args, "substring", line)
+}
+
+// `slice` on a string is the same as `substring`.
+fn slice(args: &[Value], line: usize) -> Result<Value, Error> {
+    characters(args, "slice", line)
+}
+
+/// The characters from `start` up to, but not including, `end`.
+fn characters(args: &[Value], name: &str, line: usize) -> Result<Value, Error> {
+    let string = string(&args[0], name, "First", line)?;
+    let len = string.chars().count();
+    let start = List::position(&args[1], len + 1, line)?;
+    let end = List::position(&args[2], len + 1, line)?;
+
+    if start > end {
+        let error = format!("Substring start {} is after its end {}.", start, end);
+        return Err(Error::RuntimeError(line, error));
+    }
+
+    Ok(new_string(
+        string.chars().skip(start).take(end - start).collect(),
+    ))
+}
+
+/// The character at `index`, as a one character string.
+pub fn char_at(string: &LoxString, index: &Value, line: usize) -> Result<Value, Error> {
+    let string = string.as_str();
+    let index = List::position(index, string.chars().count(), line)?;
+    let c = string.chars().nth(index).unwrap_or_default();
+    Ok(new_string(c.to_string()))
+}
+
+/// Whether `needle` appears in the string.
+fn contains(args: &[Value], line: usize) -> Result<Value, Error> {
+    let haystack = string(&args[0], "contains", "First", line)?;
+    let needle = string(&args[1], "contains", "Second", line)?;
+    Ok(Value::Boolean(haystack.contains(&needle)))
+}
+
+fn index_of(args: &[Value], line: usize) -> Result<Value, Error> {
+    let haystack = string(&args[0], "index_of", "First", line)?;
+    let needle = string(&args[1], "index_of", "Second", line)?;
+
+    let index = match haystack.find(&needle) {
+        Some(byte) => haystack[..byte].chars().count() as f64,
+        None => -1.0,
+    };
+    Ok(Value::Number(index))
+}
+
+fn starts_with(args: &[Value], line: usize) -> Result<Value, Error> {
+    let string = string(&args[0], "starts_with", "First", line)?;
+    let prefix = self::string(&args[1], "starts_with", "Second", line)?;
+    Ok(Value::Boolean(string.starts_with(&prefix)))
+}
+
+fn ends_with(args: &[Value], line: usize) -> Result<Value, Error> {
+    let string = string(&args[0], "ends_with", "First", line)?;
+    let suffix = self::string(&args[1], "ends_with", "Second", line)?;
+    Ok(Value::Boolean(string.ends_with(&suffix)))
+}
+
+fn upper(args: &[Value], line: usize) -> Result<Value, Error> {
+    let string = string(&args[0], "upper", "First", line)?;
+    Ok(new_string(string.to_uppercase()))
+}
+
+fn lower(args: &[Value], line: usize) -> Result<Value, Error> {
+    let string = string(&args[0], "lower", "First", line)?;
+    Ok(new_string(string.to_lowercase()))
+}
+
+fn trim(args: &[Value], line: usize) -> Result<Value, Error> {
+    let string = string(&args[0], "trim", "First", line)?;
+    Ok(new_string(string.trim().to_string()))
+}
+
+/// Splits on every `separator`, or into characters if it's empty.
+fn split(args: &[Value], line: usize) -> Result<Value, Error> {
+    let string = string(&args[0], "split", "First", line)?;
+    let separator = self::string(&args[1], "split", "Second", line)?;
+
+    let parts: Vec<Value> = if separator.is_empty() {
+        string.chars().map(|c| new_string(c.to_string())).collect()
+    } else {
+        string
+            .split(&separator)
+            .map(|part| new_string(part.to_string()))
+            .collect()
+    };
+    Ok(Value::List(List::new(parts)))
+}
+
+fn replace(args: &[Value], line: usize) -> Result<Value, Error> {
+    let string = string(&args[0], "replace", "First", line)?;
+    let from = self::string(&args[1], "replace", "Second", line)?;
+    let to = self::string(&args[2], "replace", "Third", line)?;
+
+    if from.is_empty() {
+        return Err(Error::RuntimeError(
+            line,
+            "Can't replace an empty string.".to_string(),
+        ));
+    }
+    Ok(new_string(string.replace(&from, &to)))
+}
+
+fn repeat(args: &[Value], line: usize) -> Result<Value, Error> {
+    let string = string(&args[0], "repeat", "First", line)?;
+    let count = match &args[1] {
+        Value::Number(count) if count.fract() == 0.0 && *count >= 0.0 => *count as usize,
+        _ => {
+            return Err(Error::RuntimeError(
+                line,
+                "Repeat count must be a non-negative integer.".to_string(),
+            ));
+        }
+    };
+    match string.len().checked_mul(count) {
+        Some(len) if len <= MAX_REPEAT_LEN => Ok(new_string(string.repeat(count))),
+        _ => Err(Error::RuntimeError(
+            line,
+            "Repeat count too large.".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call_repeat(string: &str, count: f64) -> Result<Value, Error> {
+        repeat(
+            &[Value::String(LoxString::from(string)), Value::Number(count)],
+            3,
+        )
+    }
+
+    #[test]
+    fn repeat_builds_the_string() {
+        assert_eq!(call_repeat("ab", 3.0).unwrap().to_string(), "ababab");
+        assert_eq!(call_repeat("ab", 0.0).unwrap().to_string(), "");
+    }
+
+    #[test]
+    fn repeat_refuses_counts_too_large_to_build() {
+        for count in [4294967296.0 * 4294967296.0, (MAX_REPEAT_LEN + 1) as f64] {
+            match call_repeat("ab", count) {
+                Err(Error::RuntimeError(3, message)) => {
+                    assert_eq!(message, "Repeat count too large.")
+                }
+                other => panic!("expected a runtime error, got {:?}", other),
+            }
+        }
+        // An empty string repeats to nothing however often it's repeated.
+        assert_eq!(call_repeat("", 1e300).unwrap().to_string(), "");
+    }
+}
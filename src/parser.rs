@@ -1,8 +1,9 @@
 use crate::enums::error::Error;
-use crate::enums::expression::{Expression, Operator, Primary, Unary};
+use crate::enums::expression::{Expression, Fixity, Operator, Primary, Unary};
 use crate::enums::statement::Statement;
-use crate::enums::token::{Lexeme, Token, TokenStream};
-use crate::tokenizer::tokenize;
+use crate::enums::token::{Lexeme, Span, StringPart, Token, TokenStream};
+use crate::tokenizer::{scan, tokenize};
+use crate::utils::get_file_contents;
 use std::collections::VecDeque;
 
 pub fn parse(filename: &str) -> (Vec<Expression>, Vec<Error>) {
@@ -27,7 +28,12 @@ pub fn parse(filename: &str) -> (Vec<Expression>, Vec<Error>) {
 }
 
 pub fn parse_statements(filename: &str) -> (Vec<Statement>, Vec<Error>) {
-    let (tokens, _) = tokenize(filename);
+    parse_source(&get_file_contents(filename))
+}
+
+/// Parses a whole program from source text rather than a file.
+pub fn parse_source(source: &str) -> (Vec<Statement>, Vec<Error>) {
+    let (tokens, _) = scan(source, 1);
 
     let mut statements: Vec<Statement> = Vec::new();
     let tokens: VecDeque<Lexeme> = tokens.into();
@@ -74,14 +80,33 @@ fn statement(tokens: &mut TokenStream) -> Result<Statement, Error> {
         return if_statement(tokens);
     }
 
+    // Declarations keep the doc comment written above their keyword.
+    let doc = tokens.doc();
+
     if tokens.match_advance(&Token::Var) {
-        return var_declaration(tokens);
+        return var_declaration(tokens, doc);
+    }
+
+    if tokens.match_advance(&Token::Const) {
+        return const_declaration(tokens, doc);
     }
 
     if tokens.match_advance(&Token::Return) {
         return return_statement(tokens);
     }
 
+    if tokens.peek_is(&Token::Throw) {
+        return throw_statement(tokens);
+    }
+
+    if tokens.match_advance(&Token::Try) {
+        return try_statement(tokens);
+    }
+
+    if tokens.peek_is(&Token::Import) {
+        return import_statement(tokens);
+    }
+
     if tokens.match_advance(&Token::While) {
         return while_statement(tokens);
     }
@@ -90,8 +115,10 @@ fn statement(tokens: &mut TokenStream) -> Result<Statement, Error> {
         return for_statement(tokens);
     }
 
-    if tokens.match_advance(&Token::Fun) {
-        return fn_statement(tokens);
+    // `fun (` starts an anonymous function, which is an expression.
+    if tokens.peek_is(&Token::Fun) && tokens.peek_nth(1) != Some(&Token::LeftParen) {
+        tokens.advance();
+        return fn_statement(tokens, doc);
     }
 
     let expr = expression(tokens)?;
@@ -99,6 +126,69 @@ fn statement(tokens: &mut TokenStream) -> Result<Statement, Error> {
     Ok(Statement::Expression(expr))
 }
 
+fn throw_statement(tokens: &mut TokenStream) -> Result<Statement, Error> {
+    let line = tokens.line();
+    tokens.advance();
+    let value = expression(tokens)?;
+
+    tokens.consume(&Token::SemiColon, "Expected ';' after throw.")?;
+    Ok(Statement::Throw(value, line))
+}
+
+fn import_statement(tokens: &mut TokenStream) -> Result<Statement, Error> {
+    let line = tokens.line();
+    tokens.advance();
+
+    let path = match tokens.peek() {
+        Some(Token::String(_, path)) => path.clone(),
+        _ => return Err(tokens.error("Expected a path string after 'import'.")),
+    };
+    tokens.advance();
+
+    let mut alias = None;
+    if tokens.match_advance(&Token::As) {
+        alias = Some(tokens.consume_identifier("Expected a name after 'as'.")?);
+    }
+
+    tokens.consume(&Token::SemiColon, "Expected ';' after import.")?;
+    Ok(Statement::Import(path, alias, line))
+}
+
+fn try_statement(tokens: &mut TokenStream) -> Result<Statement, Error> {
+    let body = braced_block(tokens, "Expected '{' after 'try'.")?;
+
+    let mut catch = None;
+    if tokens.match_advance(&Token::Catch) {
+        tokens.consume(&Token::LeftParen, "Expected '(' after 'catch'.")?;
+        let name = tokens.consume_identifier("Expected a name for the caught error.")?;
+        tokens.consume(&Token::RightParen, "Expected ')' after the caught error.")?;
+        let handler = braced_block(tokens, "Expected '{' after catch.")?;
+        catch = Some((name, Box::new(handler)));
+    }
+
+    let mut finally = None;
+    if tokens.match_advance(&Token::Finally) {
+        finally = Some(Box::new(braced_block(
+            tokens,
+            "Expected '{' after 'finally'.",
+        )?));
+    }
+
+    if catch.is_none() && finally.is_none() {
+        return Err(tokens.error("Expected 'catch' or 'finally' after try block."));
+    }
+
+    Ok(Statement::Try(Box::new(body), catch, finally))
+}
+
+fn braced_block(tokens: &mut TokenStream, message: &str) -> Result<Statement, Error> {
+    if !tokens.peek_is(&Token::LeftBrace) {
+        return Err(tokens.error(message));
+    }
+
+    block(tokens)
+}
+
 fn return_statement(tokens: &mut TokenStream) -> Result<Statement, Error> {
     let mut value = Expression::Primary(Primary::Nil);
 
@@ -110,8 +200,15 @@ fn return_statement(tokens: &mut TokenStream) -> Result<Statement, Error> {
     Ok(Statement::Return(value))
 }
 
-fn fn_statement(tokens: &mut TokenStream) -> Result<Statement, Error> {
+fn fn_statement(tokens: &mut TokenStream, doc: Option<String>) -> Result<Statement, Error> {
     let identifier = tokens.consume_identifier("Function name expected")?;
+    let (params, block) = function_body(tokens)?;
+
+    Ok(Statement::Fn(identifier, params, Box::new(block), doc))
+}
+
+// The parameter list and block shared by declarations and `fun` expressions.
+fn function_body(tokens: &mut TokenStream) -> Result<(Vec<Token>, Statement), Error> {
     tokens.consume(&Token::LeftParen, "Error at fn expected '('")?;
 
     let mut params: Vec<Token> = Vec::new();
@@ -120,63 +217,115 @@ fn fn_statement(tokens: &mut TokenStream) -> Result<Statement, Error> {
         if !params.is_empty() {
             tokens.consume(&Token::Comma, "Expected comma splitting function arguments")?;
         }
-        let token = tokens.advance().unwrap();
+        let Some(token) = tokens.advance() else {
+            return Err(tokens.error("Expected ')' after parameters."));
+        };
         params.push(token);
     }
 
     tokens.consume(&Token::RightParen, "Error at fn expected ')'")?;
 
     if !tokens.peek_is(&Token::LeftBrace) {
-        return Err(Error::ParseError(
-            tokens.current_line(),
-            "Expected '{' after function declaration".to_string(),
-        ));
+        return Err(tokens.error("Expected '{' after function declaration"));
     }
 
     let block = block(tokens)?;
 
-    Ok(Statement::Fn(identifier, params, Box::new(block)))
+    Ok((params, block))
+}
+
+/// Whether the tokens after an opening paren are an arrow function's
+/// parameter list, like `a, b) =>`, rather than a grouping.
+fn is_arrow_function(tokens: &TokenStream) -> bool {
+    let mut n = 0;
+    loop {
+        match tokens.peek_nth(n) {
+            Some(Token::RightParen) if n == 0 => break,
+            Some(Token::Identifier(_)) => match tokens.peek_nth(n + 1) {
+                Some(Token::Comma) => n += 2,
+                Some(Token::RightParen) => {
+                    n += 1;
+                    break;
+                }
+                _ => return false,
+            },
+            _ => return false,
+        }
+    }
+
+    tokens.peek_nth(n + 1) == Some(&Token::Arrow)
+}
+
+fn arrow_function(tokens: &mut TokenStream) -> Result<Expression, Error> {
+    let mut params: Vec<Token> = Vec::new();
+
+    while let Some(Token::Identifier(_)) = tokens.peek() {
+        params.push(tokens.advance().unwrap());
+        tokens.match_advance(&Token::Comma);
+    }
+
+    tokens.consume(&Token::RightParen, "Expected ')' after parameters.")?;
+    tokens.consume(&Token::Arrow, "Expected '=>' after parameters.")?;
+    let body = assignment(tokens)?;
+
+    Ok(Expression::Lambda(
+        params,
+        Box::new(Statement::Return(body)),
+    ))
 }
 
 fn for_statement(tokens: &mut TokenStream) -> Result<Statement, Error> {
     tokens.consume(&Token::LeftParen, "Expected '(' after statement.")?;
-    let _ = tokens.consume(&Token::Var, "Error at var expected declaration after for.");
 
-    let statement = match var_declaration(tokens) {
-        Ok(statement) => Some(Box::new(statement)),
-        Err(_) => {
-            tokens.consume(&Token::SemiColon, "Expected ';'.")?;
-            None
-        }
+    let skip = usize::from(tokens.peek_is(&Token::Var));
+    if matches!(tokens.peek_nth(skip), Some(Token::Identifier(_)))
+        && tokens.peek_nth(skip + 1) == Some(&Token::In)
+    {
+        return for_in_statement(tokens);
+    }
+
+    // Each clause may be left out.
+    let statement = if tokens.match_advance(&Token::SemiColon) {
+        None
+    } else if tokens.match_advance(&Token::Var) {
+        Some(Box::new(var_declaration(tokens, None)?))
+    } else {
+        let expr = expression(tokens)?;
+        tokens.consume(&Token::SemiColon, "Expected ';' after loop initializer.")?;
+        Some(Box::new(Statement::Expression(expr)))
     };
 
-    let check = match matches!(
-        tokens.peek().unwrap_or(&Token::Unknown),
-        Token::Identifier(_)
-    ) {
-        true => {
-            let expr = expression(tokens)?;
-            tokens.consume(&Token::SemiColon, "Expected ';'.")?;
-            Some(expr)
-        }
-        false => None,
+    let check = match tokens.peek_is(&Token::SemiColon) {
+        true => None,
+        false => Some(expression(tokens)?),
     };
+    tokens.consume(&Token::SemiColon, "Expected ';' after loop condition.")?;
 
-    let increment = match matches!(
-        tokens.peek().unwrap_or(&Token::Unknown),
-        Token::Identifier(_)
-    ) {
-        true => Some(expression(tokens)?),
-        false => None,
+    let increment = match tokens.peek_is(&Token::RightParen) {
+        true => None,
+        false => Some(expression(tokens)?),
     };
 
-    tokens.consume(&Token::RightParen, "Expected ')' after conditional.")?;
+    tokens.consume(&Token::RightParen, "Expected ')' after for clauses.")?;
 
     let block = block(tokens)?;
 
     Ok(Statement::For(statement, check, increment, Box::new(block)))
 }
 
+fn for_in_statement(tokens: &mut TokenStream) -> Result<Statement, Error> {
+    tokens.match_advance(&Token::Var);
+    let name = tokens.consume_identifier("Expected loop variable name.")?;
+    let line = tokens.line();
+    tokens.consume(&Token::In, "Expected 'in' after loop variable.")?;
+    let iterable = expression(tokens)?;
+    tokens.consume(&Token::RightParen, "Expected ')' after for-in clause.")?;
+
+    let block = block(tokens)?;
+
+    Ok(Statement::ForIn(name, iterable, Box::new(block), line))
+}
+
 fn while_statement(tokens: &mut TokenStream) -> Result<Statement, Error> {
     let expr = conditional_expression(tokens)?;
     let statement = block(tokens)?;
@@ -209,7 +358,7 @@ fn print_statement(tokens: &mut TokenStream) -> Result<Statement, Error> {
     Ok(Statement::Print(expr))
 }
 
-fn var_declaration(tokens: &mut TokenStream) -> Result<Statement, Error> {
+fn var_declaration(tokens: &mut TokenStream, doc: Option<String>) -> Result<Statement, Error> {
     let name = tokens.consume_identifier("Expected variable name.")?;
 
     let initializer = if tokens.match_advance(&Token::Equal) {
@@ -223,7 +372,23 @@ fn var_declaration(tokens: &mut TokenStream) -> Result<Statement, Error> {
         "Expected ';' after variable declaration.",
     )?;
 
-    Ok(Statement::Declaration(name, initializer))
+    Ok(Statement::Declaration(name, initializer, doc))
+}
+
+fn const_declaration(tokens: &mut TokenStream, doc: Option<String>) -> Result<Statement, Error> {
+    let name = tokens.consume_identifier("Expected constant name.")?;
+
+    if !tokens.match_advance(&Token::Equal) {
+        return Err(tokens.error(format!("Constant '{}' must be initialized.", name)));
+    }
+    let initializer = expression(tokens)?;
+
+    tokens.consume(
+        &Token::SemiColon,
+        "Expected ';' after constant declaration.",
+    )?;
+
+    Ok(Statement::Const(name, initializer, doc))
 }
 
 fn expression(tokens: &mut TokenStream) -> Result<Expression, Error> {
@@ -231,39 +396,131 @@ fn expression(tokens: &mut TokenStream) -> Result<Expression, Error> {
 }
 
 fn assignment(tokens: &mut TokenStream) -> Result<Expression, Error> {
-    let left = logical_or(tokens)?;
+    let left = conditional(tokens)?;
 
     if tokens.peek_is(&Token::Equal) {
+        let line = tokens.line();
+        let span = tokens.span();
         tokens.advance();
         let right = assignment(tokens)?;
 
         match left {
-            Expression::Primary(Primary::Identifier(name)) => {
-                return Ok(Expression::Assignment(
-                    Primary::Identifier(name),
+            Expression::Primary(identifier @ Primary::Identifier(..)) => {
+                return Ok(Expression::Assignment(identifier, Box::new(right), line));
+            }
+            Expression::Index(list, index, line) => {
+                return Ok(Expression::IndexAssignment(
+                    list,
+                    index,
                     Box::new(right),
+                    line,
                 ));
             }
             _ => {
                 return Err(Error::ParseError(
-                    tokens.current_line(),
+                    line,
                     "Invalid assignment".to_string(),
+                    span,
                 ));
             }
         }
     }
 
+    if let Some(operator) = tokens.peek().and_then(compound_operator) {
+        let line = tokens.line();
+        let span = tokens.span();
+        tokens.advance();
+        let target = assignment_target(left, line, span)?;
+        let right = assignment(tokens)?;
+
+        return Ok(Expression::CompoundAssignment(
+            Box::new(target),
+            operator,
+            Box::new(right),
+            line,
+        ));
+    }
+
     Ok(left)
 }
 
+fn compound_operator(token: &Token) -> Option<Operator> {
+    match token {
+        Token::PlusEqual => Some(Operator::Plus),
+        Token::MinusEqual => Some(Operator::Minus),
+        Token::StarEqual => Some(Operator::Star),
+        Token::DivisionEqual => Some(Operator::Division),
+        Token::PercentEqual => Some(Operator::Modulo),
+        _ => None,
+    }
+}
+
+fn increment_operator(token: &Token) -> Option<Operator> {
+    match token {
+        Token::PlusPlus => Some(Operator::Plus),
+        Token::MinusMinus => Some(Operator::Minus),
+        _ => None,
+    }
+}
+
+// Compound assignment and `++`/`--` both read and then write their target,
+// so it has to be something that can be assigned to.
+fn assignment_target(
+    target: Expression,
+    line: usize,
+    span: Option<Span>,
+) -> Result<Expression, Error> {
+    match target {
+        Expression::Primary(Primary::Identifier(..)) | Expression::Index(..) => Ok(target),
+        _ => Err(Error::ParseError(
+            line,
+            "Invalid assignment".to_string(),
+            span,
+        )),
+    }
+}
+
+fn conditional(tokens: &mut TokenStream) -> Result<Expression, Error> {
+    let condition = coalesce(tokens)?;
+
+    if tokens.match_advance(&Token::Question) {
+        let then = assignment(tokens)?;
+        tokens.consume(&Token::Colon, "Expected ':' in conditional expression.")?;
+        let otherwise = conditional(tokens)?;
+
+        return Ok(Expression::Conditional(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        ));
+    }
+
+    Ok(condition)
+}
+
+fn coalesce(tokens: &mut TokenStream) -> Result<Expression, Error> {
+    let mut expr = logical_or(tokens)?;
+
+    while tokens.peek_is(&Token::QuestionQuestion) {
+        let line = tokens.line();
+        tokens.advance();
+        let right = logical_or(tokens)?;
+
+        expr = Expression::Binary(Box::new(expr), Operator::Coalesce, Box::new(right), line);
+    }
+
+    Ok(expr)
+}
+
 fn logical_or(tokens: &mut TokenStream) -> Result<Expression, Error> {
     let mut expr = logical_and(tokens)?;
 
     while tokens.peek_is(&Token::Or) {
+        let line = tokens.line();
         tokens.advance();
         let right = logical_and(tokens)?;
 
-        expr = Expression::Binary(Box::new(expr), Operator::Or, Box::new(right));
+        expr = Expression::Binary(Box::new(expr), Operator::Or, Box::new(right), line);
     }
 
     Ok(expr)
@@ -273,10 +530,11 @@ fn logical_and(tokens: &mut TokenStream) -> Result<Expression, Error> {
     let mut expr = equality(tokens)?;
 
     while tokens.peek_is(&Token::And) {
+        let line = tokens.line();
         tokens.advance();
         let right = equality(tokens)?;
 
-        expr = Expression::Binary(Box::new(expr), Operator::And, Box::new(right));
+        expr = Expression::Binary(Box::new(expr), Operator::And, Box::new(right), line);
     }
 
     Ok(expr)
@@ -286,11 +544,12 @@ fn equality(tokens: &mut TokenStream) -> Result<Expression, Error> {
     let mut expr = comparison(tokens)?;
 
     while tokens.peek_is(&Token::EqualEqual) || tokens.peek_is(&Token::BangEqual) {
+        let line = tokens.line();
         let operator_token = tokens.advance().unwrap();
         let op = to_equality(operator_token);
         let right = comparison(tokens)?;
 
-        expr = Expression::Binary(Box::new(expr), op, Box::new(right));
+        expr = Expression::Binary(Box::new(expr), op, Box::new(right), line);
     }
 
     Ok(expr)
@@ -304,11 +563,12 @@ fn comparison(tokens: &mut TokenStream) -> Result<Expression, Error> {
         || tokens.peek_is(&Token::Greater)
         || tokens.peek_is(&Token::GreaterEqual)
     {
+        let line = tokens.line();
         let operator_token = tokens.advance().unwrap();
         let op = to_comparison(operator_token);
         let right = addition(tokens)?;
 
-        expr = Expression::Binary(Box::new(expr), op, Box::new(right));
+        expr = Expression::Binary(Box::new(expr), op, Box::new(right), line);
     }
 
     Ok(expr)
@@ -318,11 +578,12 @@ fn addition(tokens: &mut TokenStream) -> Result<Expression, Error> {
     let mut expr = multiplication(tokens)?;
 
     while tokens.peek_is(&Token::Plus) || tokens.peek_is(&Token::Minus) {
+        let line = tokens.line();
         let operator_token = tokens.advance().unwrap();
         let op = to_operator(operator_token);
         let right = multiplication(tokens)?;
 
-        expr = Expression::Binary(Box::new(expr), op, Box::new(right));
+        expr = Expression::Binary(Box::new(expr), op, Box::new(right), line);
     }
 
     Ok(expr)
@@ -331,12 +592,17 @@ fn addition(tokens: &mut TokenStream) -> Result<Expression, Error> {
 fn multiplication(tokens: &mut TokenStream) -> Result<Expression, Error> {
     let mut expr = unary(tokens)?;
 
-    while tokens.peek_is(&Token::Star) || tokens.peek_is(&Token::Division) {
+    while tokens.peek_is(&Token::Star)
+        || tokens.peek_is(&Token::Division)
+        || tokens.peek_is(&Token::Percent)
+        || tokens.peek_is(&Token::TildeSlash)
+    {
+        let line = tokens.line();
         let operator_token = tokens.advance().unwrap();
         let op = to_operator(operator_token);
         let right = unary(tokens)?;
 
-        expr = Expression::Binary(Box::new(expr), op, Box::new(right));
+        expr = Expression::Binary(Box::new(expr), op, Box::new(right), line);
     }
 
     Ok(expr)
@@ -344,23 +610,100 @@ fn multiplication(tokens: &mut TokenStream) -> Result<Expression, Error> {
 
 fn unary(tokens: &mut TokenStream) -> Result<Expression, Error> {
     if tokens.peek_is(&Token::Bang) || tokens.peek_is(&Token::Minus) {
+        let line = tokens.line();
         let operator_token = tokens.advance().unwrap();
         let unary_op = to_unary(operator_token);
 
         let right_operand = unary(tokens)?;
 
-        Ok(Expression::Unary(unary_op, Box::new(right_operand)))
+        Ok(Expression::Unary(unary_op, Box::new(right_operand), line))
+    } else if let Some(operator) = tokens.peek().and_then(increment_operator) {
+        let line = tokens.line();
+        let span = tokens.span();
+        tokens.advance();
+        let target = assignment_target(unary(tokens)?, line, span)?;
+
+        Ok(Expression::Increment(
+            Box::new(target),
+            operator,
+            Fixity::Prefix,
+            line,
+        ))
     } else {
-        primary(tokens)
+        exponent(tokens)
+    }
+}
+
+fn exponent(tokens: &mut TokenStream) -> Result<Expression, Error> {
+    let base = postfix(tokens)?;
+
+    let line = tokens.line();
+    if tokens.match_advance(&Token::StarStar) {
+        // Parsing the right side as a unary allows `2 ** -1`, and recursing
+        // back into `exponent` from there makes `**` right-associative.
+        let power = unary(tokens)?;
+        return Ok(Expression::Binary(
+            Box::new(base),
+            Operator::Power,
+            Box::new(power),
+            line,
+        ));
+    }
+
+    Ok(base)
+}
+
+fn postfix(tokens: &mut TokenStream) -> Result<Expression, Error> {
+    let expr = call(tokens)?;
+
+    if let Some(operator) = tokens.peek().and_then(increment_operator) {
+        let line = tokens.line();
+        let span = tokens.span();
+        tokens.advance();
+        let target = assignment_target(expr, line, span)?;
+
+        return Ok(Expression::Increment(
+            Box::new(target),
+            operator,
+            Fixity::Postfix,
+            line,
+        ));
+    }
+
+    Ok(expr)
+}
+
+fn call(tokens: &mut TokenStream) -> Result<Expression, Error> {
+    let mut expr = primary(tokens)?;
+
+    loop {
+        let line = tokens.line();
+        if tokens.match_advance(&Token::LeftBracket) {
+            let index = expression(tokens)?;
+            tokens.consume(&Token::RightBracket, "Expected ']' after index.")?;
+
+            expr = Expression::Index(Box::new(expr), Box::new(index), line);
+        } else if tokens.match_advance(&Token::Dot) {
+            let name = tokens.consume_identifier("Expected a property name after '.'.")?;
+
+            expr = Expression::Get(Box::new(expr), name, line);
+        } else if tokens.match_advance(&Token::LeftParen) {
+            let args = get_params(tokens)?;
+            tokens.consume(&Token::RightParen, "Expected ')' after arguments.")?;
+
+            expr = Expression::Call(Box::new(expr), args, line);
+        } else {
+            return Ok(expr);
+        }
     }
 }
 
 fn primary(tokens: &mut TokenStream) -> Result<Expression, Error> {
+    let line = tokens.line();
+    let span = tokens.span();
     let token = match tokens.advance() {
         Some(token) => token,
-        None => {
-            panic!("End of token stream");
-        }
+        None => return Err(tokens.error("Expected expression.")),
     };
 
     match token {
@@ -368,15 +711,56 @@ fn primary(tokens: &mut TokenStream) -> Result<Expression, Error> {
         Token::True => Ok(Expression::Primary(Primary::True)),
         Token::Nil => Ok(Expression::Primary(Primary::Nil)),
         Token::Number(_, ref number) => Ok(Expression::Primary(Primary::Number(*number))),
-        Token::String(ref literal) => Ok(Expression::Primary(Primary::String(literal.to_string()))),
+        Token::Interpolation(_, parts) => interpolation(parts, line, span),
+        Token::String(_, ref literal) => {
+            Ok(Expression::Primary(Primary::String(literal.to_string())))
+        }
         Token::Identifier(identifier) => {
             if tokens.peek_is(&Token::LeftParen) {
-                return parse_function_call(identifier, tokens);
+                return parse_function_call(identifier, line, tokens);
+            }
+
+            Ok(Expression::Primary(Primary::Identifier(identifier, line)))
+        }
+
+        Token::LeftBracket => {
+            let mut elements: Vec<Expression> = Vec::new();
+
+            while tokens.peek().unwrap_or(&Token::Unknown) != &Token::RightBracket {
+                if !elements.is_empty() {
+                    tokens.consume(&Token::Comma, "Expected comma splitting list elements")?;
+                }
+                elements.push(expression(tokens)?);
             }
 
-            Ok(Expression::Primary(Primary::Identifier(identifier)))
+            tokens.consume(&Token::RightBracket, "Expected ']' after list elements.")?;
+            Ok(Expression::List(elements))
         }
 
+        // A `{` that starts a statement is always a block, so a map literal is
+        // only ever reached here, in expression position.
+        Token::LeftBrace => {
+            let mut entries: Vec<(Expression, Expression)> = Vec::new();
+
+            while tokens.peek().unwrap_or(&Token::Unknown) != &Token::RightBrace {
+                if !entries.is_empty() {
+                    tokens.consume(&Token::Comma, "Expected comma splitting map entries")?;
+                }
+                let key = expression(tokens)?;
+                tokens.consume(&Token::Colon, "Expected ':' after map key.")?;
+                let value = expression(tokens)?;
+                entries.push((key, value));
+            }
+
+            tokens.consume(&Token::RightBrace, "Expected '}' after map entries.")?;
+            Ok(Expression::Map(entries, line))
+        }
+
+        Token::Fun => {
+            let (params, body) = function_body(tokens)?;
+            Ok(Expression::Lambda(params, Box::new(body)))
+        }
+        Token::LeftParen if is_arrow_function(tokens) => arrow_function(tokens),
         Token::LeftParen => {
             let expr_inside = expression(tokens)?;
             if tokens.match_advance(&Token::RightParen) {
@@ -384,28 +768,70 @@ fn primary(tokens: &mut TokenStream) -> Result<Expression, Error> {
                     expr_inside,
                 ))))
             } else {
-                let error = Error::ParseError(
-                    tokens.current_line(),
-                    "Expected ')' after expression.".to_string(),
-                );
-                Err(error)
+                Err(tokens.error("Expected ')' after expression."))
             }
         }
 
-        _ => {
-            let error =
-                Error::ParseError(tokens.current_line(), format!("Unknown token {}", token));
-            Err(error)
+        _ => Err(Error::ParseError(
+            line,
+            format!("Unknown token {}", token),
+            span,
+        )),
+    }
+}
+
+// Errors at the end of a `${...}` are reported at the whole string, as its
+// code has no end of its own in the source.
+fn interpolation(
+    parts: Vec<StringPart>,
+    line: usize,
+    span: Option<Span>,
+) -> Result<Expression, Error> {
+    let mut expressions: Vec<Expression> = Vec::new();
+
+    for part in parts {
+        match part {
+            StringPart::Text(text) => expressions.push(Expression::Primary(Primary::String(text))),
+            StringPart::Code(lexemes) => {
+                if lexemes.is_empty() {
+                    return Err(Error::ParseError(
+                        line,
+                        "Expected expression inside '${}'.".to_string(),
+                        span,
+                    ));
+                }
+
+                let mut stream = TokenStream {
+                    tokens: lexemes.into(),
+                };
+                let expression = expression(&mut stream).map_err(|error| match error {
+                    Error::ParseError(_, message, None) => Error::ParseError(line, message, span),
+                    error => error,
+                })?;
+                expressions.push(expression);
+
+                if !stream.is_at_end() {
+                    return Err(stream.error("Expected '}' after interpolated expression."));
+                }
+            }
         }
     }
+
+    Ok(Expression::Interpolation(expressions))
 }
 
-fn parse_function_call(identifier: String, tokens: &mut TokenStream) -> Result<Expression, Error> {
+fn parse_function_call(
+    identifier: String,
+    line: usize,
+    tokens: &mut TokenStream,
+) -> Result<Expression, Error> {
     tokens.consume(&Token::LeftParen, "Expected '(' after function name.")?;
     let params = get_params(tokens)?;
     tokens.consume(&Token::RightParen, "Expected ')' after arguments.")?;
 
-    Ok(Expression::Primary(Primary::Function(identifier, params)))
+    Ok(Expression::Primary(Primary::Function(
+        identifier, params, line,
+    )))
 }
 
 fn get_params(tokens: &mut TokenStream) -> Result<Vec<Expression>, Error> {
@@ -435,6 +861,8 @@ fn to_operator(token: Token) -> Operator {
     match token {
         Token::Star => Operator::Star,
         Token::Division => Operator::Division,
+        Token::Percent => Operator::Modulo,
+        Token::TildeSlash => Operator::IntegerDivision,
         Token::Minus => Operator::Minus,
         Token::Plus => Operator::Plus,
         _ => panic!("Expected binary operator, got {:?}", token),
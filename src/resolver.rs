@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use crate::enums::error::Error;
+use crate::enums::expression::{Expression, Primary};
+use crate::enums::statement::Statement;
+use crate::enums::token::Token;
+
+#[derive(Clone, Copy)]
+enum Binding {
+    Variable,
+    Constant,
+    Function,
+}
+
+/// Walks a program before it runs and reports assignments to constants and
+/// functions that are visible from the code alone. Anything it can't see,
+/// like a global declared after the function that assigns to it, is left to
+/// the check in `Environment::assign`.
+pub fn resolve(statements: &[Statement]) -> Vec<Error> {
+    let mut resolver = Resolver {
+        scopes: vec![HashMap::new()],
+        errors: Vec::new(),
+    };
+
+    for statement in statements {
+        resolver.statement(statement);
+    }
+    resolver.errors
+}
+
+struct Resolver {
+    scopes: Vec<HashMap<String, Binding>>,
+    errors: Vec<Error>,
+}
+
+impl Resolver {
+    fn declare(&mut self, name: &str, binding: Binding) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), binding);
+        }
+    }
+
+    fn scoped(&mut self, names: &[Token], body: impl FnOnce(&mut Resolver)) {
+        self.scopes.push(HashMap::new());
+        for name in names {
+            self.declare(&name.get_identifier(), Binding::Variable);
+        }
+        body(self);
+        self.scopes.pop();
+    }
+
+    fn assign(&mut self, name: &str, line: usize) {
+        let binding = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied());
+
+        let error = match binding {
+            Some(Binding::Constant) => format!("Can't assign to constant '{}'.", name),
+            Some(Binding::Function) => format!("Can't assign to function '{}'.", name),
+            Some(Binding::Variable) | None => return,
+        };
+        self.errors.push(Error::CompileError(line, error));
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Block(statements) => self.scoped(&[], |resolver| {
+                for statement in statements {
+                    resolver.statement(statement);
+                }
+            }),
+            Statement::Declaration(name, initializer, _) => {
+                self.expression(initializer);
+                self.declare(name, Binding::Variable);
+            }
+            Statement::Const(name, initializer, _) => {
+                self.expression(initializer);
+                self.declare(name, Binding::Constant);
+            }
+            Statement::Expression(expression)
+            | Statement::Print(expression)
+            | Statement::Return(expression)
+            | Statement::Throw(expression, _) => self.expression(expression),
+            Statement::IfElse(condition, then, otherwise) => {
+                self.expression(condition);
+                self.statement(then);
+                if let Some(otherwise) = otherwise {
+                    self.statement(otherwise);
+                }
+            }
+            Statement::While(condition, body) => {
+                self.expression(condition);
+                self.statement(body);
+            }
+            // The initializer runs in the enclosing scope, like the loop
+            // itself does.
+            Statement::For(initializer, condition, increment, body) => {
+                if let Some(initializer) = initializer {
+                    self.statement(initializer);
+                }
+                for expression in condition.iter().chain(increment) {
+                    self.expression(expression);
+                }
+                self.scoped(&[], |resolver| resolver.statement(body));
+            }
+            Statement::ForIn(name, iterable, body, _) => {
+                self.expression(iterable);
+                self.scoped(&[], |resolver| {
+                    resolver.declare(name, Binding::Variable);
+                    resolver.statement(body);
+                });
+            }
+            Statement::Fn(name, params, body, _) => {
+                self.declare(name, Binding::Function);
+                self.scoped(params, |resolver| resolver.statement(body));
+            }
+            Statement::Try(body, catch, finally) => {
+                self.statement(body);
+                if let Some((name, handler)) = catch {
+                    self.scoped(&[], |resolver| {
+                        resolver.declare(name, Binding::Variable);
+                        resolver.statement(handler);
+                    });
+                }
+                if let Some(finally) = finally {
+                    self.statement(finally);
+                }
+            }
+            Statement::Import(_, alias, _) => {
+                if let Some(alias) = alias {
+                    self.declare(alias, Binding::Variable);
+                }
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Assignment(Primary::Identifier(name, _), value, line) => {
+                self.expression(value);
+                self.assign(name, *line);
+            }
+            Expression::Assignment(_, value, _) => self.expression(value),
+            Expression::CompoundAssignment(target, _, value, line) => {
+                self.target(target, *line);
+                self.expression(value);
+            }
+            Expression::Increment(target, _, _, line) => self.target(target, *line),
+            Expression::Binary(left, _, right, _) => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::Unary(_, operand, _) => self.expression(operand),
+            Expression::Primary(Primary::Grouping(inner)) => self.expression(inner),
+            Expression::Primary(Primary::Function(_, args, _)) => self.expressions(args),
+            Expression::Primary(_) => {}
+            Expression::Conditional(condition, then, otherwise) => {
+                self.expression(condition);
+                self.expression(then);
+                self.expression(otherwise);
+            }
+            Expression::Lambda(params, body) => {
+                self.scoped(params, |resolver| resolver.statement(body));
+            }
+            Expression::Call(callee, args, _) => {
+                self.expression(callee);
+                self.expressions(args);
+            }
+            Expression::Interpolation(parts) | Expression::List(parts) => self.expressions(parts),
+            Expression::Map(entries, _) => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            Expression::Index(object, index, _) => {
+                self.expression(object);
+                self.expression(index);
+            }
+            Expression::Get(object, _, _) => self.expression(object),
+            Expression::IndexAssignment(object, index, value, _) => {
+                self.expression(object);
+                self.expression(index);
+                self.expression(value);
+            }
+        }
+    }
+
+    fn expressions(&mut self, expressions: &[Expression]) {
+        for expression in expressions {
+            self.expression(expression);
+        }
+    }
+
+    fn target(&mut self, target: &Expression, line: usize) {
+        match target {
+            Expression::Primary(Primary::Identifier(name, _)) => self.assign(name, line),
+            target => self.expression(target),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+
+    fn errors(source: &str) -> Vec<(usize, String)> {
+        let (statements, errors) = parse_source(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        resolve(&statements)
+            .into_iter()
+            .map(|error| match error {
+                Error::CompileError(line, message) => (line, message),
+                error => panic!("unexpected {:?}", error),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn assigning_to_constants_and_functions_is_an_error() {
+        assert_eq!(
+            errors("const c = 1;\n\nc = 2;"),
+            [(3, "Can't assign to constant 'c'.".to_string())]
+        );
+        assert_eq!(
+            errors("fun f() {}\nf += 1;\nf++;"),
+            [
+                (2, "Can't assign to function 'f'.".to_string()),
+                (3, "Can't assign to function 'f'.".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn shadowing_variables_can_be_assigned() {
+        assert!(errors("const c = 1;\n{\n  var c = 2;\n  c = 3;\n}").is_empty());
+        assert!(errors("fun f(c) { c = 1; }\nconst c = 2;").is_empty());
+        assert!(errors("const c = 1;\ntry {} catch (c) { c = 2; }").is_empty());
+    }
+
+    #[test]
+    fn constants_are_checked_inside_functions_and_loops() {
+        assert_eq!(errors("const c = 1;\nfun f() {\n  c = 2;\n}").len(), 1);
+        assert_eq!(errors("const c = 1;\nfor (x in [1]) c = x;").len(), 1);
+    }
+}
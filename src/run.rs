@@ -1,40 +1,93 @@
+use std::cell::Cell;
 use std::process;
+use std::rc::Rc;
 
-use crate::enums::environment::{Env, Environment, Symbol};
+use crate::enums::environment::{Env, Environment, Function, Symbol};
 use crate::enums::error::Error;
+use crate::enums::lox_string::LoxString;
+use crate::enums::map::{Key, Map, OrderedMap};
 use crate::enums::statement::Statement;
-use crate::evaluator::{Value, evaluate, truthy};
-use crate::parser::parse_statements;
+use crate::evaluator::{Value, call_function, evaluate, iterate, tail_call, truthy};
+use crate::import;
+use crate::parser::parse_source;
+use crate::resolver::resolve;
+use crate::tokenizer::scan;
+use crate::utils::get_file_contents;
 
 #[derive(Debug)]
 pub enum ControlFlow {
     Return(Value),
+    TailCall(TailCall),
     Runtime(Error),
 }
 
+/// A call in tail position whose callee and arguments are already resolved,
+/// to be run in place of the returning function's frame.
+#[derive(Debug)]
+pub struct TailCall {
+    pub function: Rc<Function>,
+    pub args: Vec<Value>,
+    pub line: usize,
+}
+
+thread_local! {
+    static SILENT: Cell<bool> = const { Cell::new(false) };
+}
+
 pub fn run(filename: &str) {
-    let (statements, errors) = parse_statements(filename);
+    if let Err(code) = interpret(filename) {
+        process::exit(code);
+    }
+}
+
+/// Runs a script, reporting any error on stderr, and returns the exit code
+/// to fail with.
+pub fn interpret(filename: &str) -> Result<(), i32> {
+    let source = get_file_contents(filename);
+    // The parser skips text the tokenizer rejected, so that's reported
+    // separately.
+    let (_, scan_errors) = scan(&source, 1);
+    for error in &scan_errors {
+        eprintln!("{}", error);
+    }
+    let (statements, errors) = parse_source(&source);
+    for error in &errors {
+        eprintln!("{}", error);
+    }
+    if !scan_errors.is_empty() || !errors.is_empty() {
+        return Err(65);
+    }
 
+    let errors = resolve(&statements);
     if !errors.is_empty() {
-        process::exit(65);
+        for error in errors {
+            eprintln!("{}", error);
+        }
+        return Err(65);
     }
 
+    import::reset(filename);
     let mut environment = Environment::new();
 
     match evaluate_statements(statements, &mut environment) {
-        Ok(()) => {}
+        Ok(()) => Ok(()),
         Err(ControlFlow::Runtime(err)) => {
             eprintln!("{}", err);
-            process::exit(65);
+            Err(65)
         }
-        Err(ControlFlow::Return(_)) => {
+        Err(ControlFlow::Return(_)) | Err(ControlFlow::TailCall(_)) => {
             eprintln!("Can't return from top-level code.");
-            process::exit(70);
+            Err(70)
         }
     }
 }
 
-fn evaluate_statements(
+/// Discards the output of `print` statements while `silent` is set.
+pub fn set_silent(silent: bool) {
+    SILENT.with(|cell| cell.set(silent));
+}
+
+pub fn evaluate_statements(
     statements: Vec<Statement>,
     environment: &mut Env,
 ) -> Result<(), ControlFlow> {
@@ -48,7 +101,9 @@ pub fn evaluate_statement(statement: Statement, environment: &mut Env) -> Result
     match statement {
         Statement::Print(expr) => {
             let value = evaluate(&expr, environment).map_err(ControlFlow::Runtime)?;
-            println!("{}", value);
+            if !SILENT.with(Cell::get) {
+                println!("{}", value);
+            }
             Ok(())
         }
 
@@ -57,11 +112,19 @@ pub fn evaluate_statement(statement: Statement, environment: &mut Env) -> Result
             Ok(())
         }
 
-        Statement::Declaration(name, expr) => {
+        Statement::Declaration(name, expr, _) => {
+            let value = evaluate(&expr, environment).map_err(ControlFlow::Runtime)?;
+            environment
+                .borrow_mut()
+                .define(name, Symbol::variable(value));
+            Ok(())
+        }
+
+        Statement::Const(name, expr, _) => {
             let value = evaluate(&expr, environment).map_err(ControlFlow::Runtime)?;
             environment
                 .borrow_mut()
-                .define(name, Symbol::Variable(value));
+                .define(name, Symbol::constant(value));
             Ok(())
         }
 
@@ -113,15 +176,391 @@ pub fn evaluate_statement(statement: Statement, environment: &mut Env) -> Result
             Ok(())
         }
 
-        Statement::Fn(name, params, body) => {
-            let function = Symbol::Function(params, *body);
-            environment.borrow_mut().define(name, function);
+        Statement::ForIn(name, iterable, body, line) => {
+            let iterable = evaluate(&iterable, environment).map_err(ControlFlow::Runtime)?;
+
+            for value in iterate(&iterable, line).map_err(ControlFlow::Runtime)? {
+                let value = value.map_err(ControlFlow::Runtime)?;
+                // A fresh scope per iteration, so closures made in the body
+                // each capture their own value.
+                let mut body_env = Environment::with_enclosing(environment.clone());
+                body_env
+                    .borrow_mut()
+                    .define(name.clone(), Symbol::variable(value));
+                evaluate_statement(*body.clone(), &mut body_env)?;
+            }
+
+            Ok(())
+        }
+
+        Statement::Fn(name, params, body, _) => {
+            let function = Function::new(name.clone(), params, *body, environment.clone());
+            environment
+                .borrow_mut()
+                .define(name, Symbol::Function(function));
             Ok(())
         }
 
         Statement::Return(expr) => {
+            Err(tail_call(&expr, environment).unwrap_or_else(ControlFlow::Runtime))
+        }
+
+        Statement::Import(path, alias, line) => {
+            import::import(&path, alias, line, environment).map_err(ControlFlow::Runtime)
+        }
+
+        Statement::Throw(expr, line) => {
             let value = evaluate(&expr, environment).map_err(ControlFlow::Runtime)?;
-            Err(ControlFlow::Return(value))
+            Err(ControlFlow::Runtime(Error::Thrown(value, line)))
+        }
+
+        Statement::Try(body, catch, finally) => {
+            let result = match (settle(evaluate_statement(*body, environment)), catch) {
+                (Err(ControlFlow::Runtime(error)), Some((name, handler))) => {
+                    let mut catch_env = Environment::with_enclosing(environment.clone());
+                    catch_env
+                        .borrow_mut()
+                        .define(name, Symbol::variable(error_value(error)));
+                    settle(evaluate_statement(*handler, &mut catch_env))
+                }
+                (result, _) => result,
+            };
+
+            // `finally` runs however the blocks above were left. If it
+            // returns or throws itself, that replaces what they did.
+            if let Some(finally) = finally {
+                evaluate_statement(*finally, environment)?;
+            }
+            result
+        }
+    }
+}
+
+/// Runs a pending tail call straight away. Inside a `try`, the call has to
+/// finish before the handlers and `finally` run, rather than replacing the
+/// frame they belong to.
+fn settle(result: Result<(), ControlFlow>) -> Result<(), ControlFlow> {
+    match result {
+        Err(ControlFlow::TailCall(call)) => {
+            match call_function(call.function, call.args, call.line) {
+                Ok(value) => Err(ControlFlow::Return(value)),
+                Err(error) => Err(ControlFlow::Runtime(error)),
+            }
         }
+        result => result,
+    }
+}
+
+/// What a `catch` binds: a thrown value as is, or for an error raised by the
+/// interpreter, a map with its `message` and `line`.
+fn error_value(error: Error) -> Value {
+    let (line, message) = match error {
+        Error::Thrown(value, _) => return value,
+        Error::RuntimeError(line, message)
+        | Error::CompileError(line, message)
+        | Error::ParseError(line, message, _) => (line, message),
+    };
+
+    let mut map = OrderedMap::default();
+    map.insert(
+        Key::String(LoxString::intern("message")),
+        Value::String(LoxString::from(message)),
+    );
+    map.insert(
+        Key::String(LoxString::intern("line")),
+        Value::Number(line as f64),
+    );
+    Value::Map(Map::new(map))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::{env, fs, process, thread};
+
+    use super::*;
+    use crate::enums::environment::unpack;
+    use crate::parser::parse_source;
+
+    // Runs a program and returns its global environment.
+    fn run_source(source: &str) -> Result<Env, ControlFlow> {
+        let (statements, errors) = parse_source(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let mut environment = Environment::new();
+        evaluate_statements(statements, &mut environment)?;
+        Ok(environment)
+    }
+
+    // Runs a program that should fail, and returns the error's message.
+    fn runtime_error(source: &str) -> String {
+        match run_source(source) {
+            Err(ControlFlow::Runtime(Error::RuntimeError(_, message))) => message,
+            other => panic!("expected a runtime error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    fn global(environment: &Env, name: &str) -> Value {
+        match environment.borrow().get(name) {
+            Some(Symbol::Variable(slot) | Symbol::Constant(slot)) => unpack(&slot),
+            other => panic!("{} isn't a variable: {:?}", name, other),
+        }
+    }
+
+    // Runs `source` on a thread with a 1 MB stack and returns `result`. The
+    // programs here nest far more calls than that holds without frame reuse.
+    fn run_on_small_stack(source: &'static str) -> String {
+        thread::Builder::new()
+            .stack_size(1 << 20)
+            .spawn(move || global(&run_source(source).unwrap(), "result").to_string())
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    #[test]
+    fn tail_calls_run_in_constant_stack() {
+        let source = "
+            fun count(n, total) {
+              if (n == 0) return total;
+              return count(n - 1, total + 1);
+            }
+            var result = count(100000, 0);
+        ";
+        assert_eq!(run_on_small_stack(source), "100000");
+    }
+
+    #[test]
+    fn any_call_in_tail_position_reuses_the_frame() {
+        let source = r#"
+            var steps = {};
+            fun next() { return steps["count"]; }
+            steps["count"] = fun (n) {
+              if (n == 0) return "lambda";
+              return next()(n - 1);
+            };
+            var result = steps["count"](100000);
+        "#;
+        assert_eq!(run_on_small_stack(source), "lambda");
+
+        let source = r#"
+            var walker = {"walk": nil};
+            walker["walk"] = (n) => n == 0 ? "member" : (walker.walk(n - 1));
+            var result = walker.walk(100000);
+        "#;
+        assert_eq!(run_on_small_stack(source), "member");
+    }
+
+    #[test]
+    fn calls_outside_tail_position_still_return_their_value() {
+        let source = "
+            fun sum(n) {
+              if (n == 0) return 0;
+              return n + sum(n - 1);
+            }
+            var result = sum(10);
+        ";
+        let environment = run_source(source).unwrap();
+        assert_eq!(global(&environment, "result"), Value::Number(55.0));
+    }
+
+    #[test]
+    fn for_in_calls_a_next_function_until_nil() {
+        let source = "
+            fun counter(limit) {
+              var i = 0;
+              return fun () {
+                if (i >= limit) return nil;
+                i = i + 1;
+                return i;
+              };
+            }
+            var seen = [];
+            for (x in counter(3)) push(seen, x);
+        ";
+        let environment = run_source(source).unwrap();
+        assert_eq!(global(&environment, "seen").to_string(), "[1, 2, 3]");
+    }
+
+    #[test]
+    fn for_in_iterates_maps_with_an_iter_function() {
+        let source = r#"
+            var countdown = {"iter": fun () {
+              var n = 3;
+              return {"next": fun () {
+                if (n == 0) return nil;
+                n = n - 1;
+                return n;
+              }};
+            }};
+            var closures = [];
+            for (x in countdown) push(closures, () => x);
+            var seen = [];
+            for (f in closures) push(seen, f());
+            var keys = [];
+            for (key in {"a": 1, "b": 2}) push(keys, key);
+        "#;
+        let environment = run_source(source).unwrap();
+        assert_eq!(global(&environment, "seen").to_string(), "[2, 1, 0]");
+        assert_eq!(global(&environment, "keys").to_string(), r#"["a", "b"]"#);
+    }
+
+    #[test]
+    fn for_in_rejects_an_iter_that_returns_no_iterator() {
+        let source = r#"for (x in {"iter": fun () { return 5; }}) print x;"#;
+        assert!(matches!(
+            run_source(source),
+            Err(ControlFlow::Runtime(Error::RuntimeError(1, _)))
+        ));
+    }
+
+    #[test]
+    fn caught_runtime_errors_carry_their_message_and_line() {
+        let source = "
+            var caught;
+            try {
+              print \"x\" + 1;
+            } catch (e) {
+              caught = e;
+            }
+        ";
+        let environment = run_source(source).unwrap();
+        assert_eq!(
+            global(&environment, "caught").to_string(),
+            r#"{"message": "Opperands must be 2 numbers or 2 strings", "line": 4}"#
+        );
+    }
+
+    #[test]
+    fn imported_constants_and_functions_stay_read_only() {
+        let path = env::temp_dir().join(format!("lox-import-test-{}.lox", process::id()));
+        fs::write(&path, "const K = 1;\nvar v = 2;\nfun f() { return 3; }\n").unwrap();
+        let import = format!("import {:?};", path.display().to_string());
+        let alias = format!("import {:?} as m;", path.display().to_string());
+
+        assert_eq!(
+            runtime_error(&format!("{} K = 5;", import)),
+            "Can't assign to constant 'K'."
+        );
+        assert_eq!(
+            runtime_error(&format!("{} f = 3;", import)),
+            "Can't assign to function 'f'."
+        );
+        assert_eq!(
+            runtime_error(&format!(r#"{} m["K"] += 5;"#, alias)),
+            "Can't assign to constant 'K'."
+        );
+        assert_eq!(
+            runtime_error(&format!(r#"{} m["f"] = nil;"#, alias)),
+            "Can't assign to function 'f'."
+        );
+        assert_eq!(
+            runtime_error(&format!(r#"{} delete(m, "K");"#, alias)),
+            "Can't delete constant 'K'."
+        );
+
+        // Variables are copied, so they can still be changed.
+        let environment = run_source(&format!(r#"{} v = 9; {} m["v"] = 7;"#, import, alias));
+        let environment = environment.unwrap();
+        assert_eq!(global(&environment, "v"), Value::Number(9.0));
+        assert_eq!(global(&environment, "K"), Value::Number(1.0));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn for_clauses_take_any_expression_or_none() {
+        let source = "
+            var seen = [];
+            for (var i = 0; i < 3; ++i) push(seen, i);
+            for (var j = 2; 0 < j; j -= 1) push(seen, j);
+            var k;
+            for (k = 5; !(k >= 7); k++) push(seen, k);
+            for (var n = -1; (n < 1); n = n + 1) push(seen, n);
+            var m = 0;
+            for (; m < 2;) m++;
+        ";
+        let environment = run_source(source).unwrap();
+        assert_eq!(
+            global(&environment, "seen").to_string(),
+            "[0, 1, 2, 2, 1, 5, 6, -1, 0]"
+        );
+        assert_eq!(global(&environment, "m"), Value::Number(2.0));
+    }
+
+    #[test]
+    fn malformed_for_clauses_are_reported() {
+        let (_, errors) = parse_source("for (var i = 0; i < 3 i++) print i;");
+        assert!(matches!(
+            &errors[..],
+            [Error::ParseError(1, message, Some(_))] if message == "Expected ';' after loop condition."
+        ));
+    }
+
+    // Writes each `(name, source)` into a fresh directory, and returns the
+    // directory.
+    fn modules(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("lox-{}-{}", test, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, source) in files {
+            fs::write(dir.join(name), source).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn modules_run_once_in_their_own_namespace() {
+        let dir = modules(
+            "modules",
+            &[
+                (
+                    "counter.lox",
+                    "var _count = 0;\nfun next() { _count++; return _count; }\nvar x = 1;\n",
+                ),
+                (
+                    "uses.lox",
+                    "import \"counter.lox\" as c;\nvar first = c.next();\n",
+                ),
+            ],
+        );
+        let source = format!(
+            "var x = 5;\nimport {:?} as u;\nimport {:?} as c;\nvar result = [u.first, c.next(), x, c.x, has(c, \"_count\"), u.c == c];",
+            dir.join("uses.lox").display().to_string(),
+            dir.join("counter.lox").display().to_string(),
+        );
+        let environment = run_source(&source).unwrap();
+        assert_eq!(
+            global(&environment, "result").to_string(),
+            "[1, 2, 5, 1, false, true]"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_and_circular_imports_are_errors() {
+        let dir = modules(
+            "cycle",
+            &[
+                ("a.lox", "import \"b.lox\";\n"),
+                ("b.lox", "import \"a.lox\";\n"),
+            ],
+        );
+        assert_eq!(
+            runtime_error("import \"no/such/module.lox\";"),
+            "Can't find module 'no/such/module.lox'."
+        );
+        let error = runtime_error(&format!(
+            "import {:?};",
+            dir.join("a.lox").display().to_string()
+        ));
+        let (a, b) = (dir.join("a.lox"), dir.join("b.lox"));
+        let (a, b) = (a.canonicalize().unwrap(), b.canonicalize().unwrap());
+        assert_eq!(
+            error,
+            format!(
+                "Circular import: {0} -> {1} -> {0}.",
+                a.display(),
+                b.display()
+            )
+        );
+        fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -0,0 +1,570 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::cst::{self, NodeKind, SyntaxElement, SyntaxNode};
+use crate::enums::expression::{Expression, Primary};
+use crate::enums::statement::Statement;
+use crate::enums::token::{Lexeme, Span, StringPart, Token};
+use crate::natives;
+use crate::tokenizer::scan;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Variable,
+    Constant,
+    Function,
+    Parameter,
+}
+
+#[derive(Clone, Debug)]
+pub struct Definition {
+    pub name: String,
+    pub kind: Kind,
+    /// Where the name is written in its declaration.
+    pub span: Span,
+    /// The source the name can be used in: the whole file for a global, or
+    /// from the declaration to the end of its block for a local.
+    pub visible: Span,
+    pub global: bool,
+    /// The parameters of a function, or of a variable that's declared as a
+    /// `fun` or arrow function.
+    pub params: Option<Vec<String>>,
+    pub doc: Option<String>,
+    /// Whether anything reads it. Assigning to it doesn't count.
+    pub used: bool,
+    /// Whether it hides a declaration from an enclosing scope.
+    pub shadows: bool,
+}
+
+/// What every name in a program refers to, worked out from the code alone,
+/// with spans into its source.
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub definitions: Vec<Definition>,
+    /// Every mention of a defined name, declarations included, with the
+    /// index of its definition.
+    pub references: Vec<(Span, usize)>,
+    /// Assignments to names nothing declares.
+    pub undeclared: Vec<(String, Span)>,
+}
+
+impl Analysis {
+    /// The definition of the name at `offset`, if there's a name there.
+    pub fn definition_at(&self, offset: usize) -> Option<usize> {
+        self.references
+            .iter()
+            .find(|(span, _)| span.start <= offset && offset <= span.end)
+            .map(|(_, definition)| *definition)
+    }
+
+    pub fn references_to(&self, definition: usize) -> impl Iterator<Item = Span> + '_ {
+        self.references
+            .iter()
+            .filter(move |(_, found)| *found == definition)
+            .map(|(span, _)| *span)
+    }
+
+    /// The definitions visible at `offset`, innermost first, leaving out any
+    /// that a closer one of the same name hides.
+    pub fn visible_at(&self, offset: usize) -> Vec<&Definition> {
+        let mut visible: Vec<&Definition> = self
+            .definitions
+            .iter()
+            .filter(|definition| {
+                definition.visible.start <= offset && offset <= definition.visible.end
+            })
+            .collect();
+        visible.sort_by_key(|definition| std::cmp::Reverse(definition.visible.start));
+
+        let mut seen = HashSet::new();
+        visible.retain(|definition| seen.insert(definition.name.as_str()));
+        visible
+    }
+}
+
+/// Resolves the names in `statements`, which were parsed from `source`. The
+/// statements can be the part of a program that parsed before an error.
+///
+/// Scopes follow the resolver's. Like the interpreter, a function can use
+/// globals declared anywhere in the file, and locals declared after it in
+/// an enclosing block.
+pub fn analyze(statements: &[Statement], source: &str) -> Analysis {
+    let mut binder = Binder {
+        locator: Locator::new(source),
+        scopes: vec![HashMap::new()],
+        globals: HashSet::new(),
+        open_imports: false,
+        functions: 0,
+        pending: Vec::new(),
+        analysis: Analysis::default(),
+    };
+
+    for statement in statements {
+        match statement {
+            Statement::Declaration(name, ..)
+            | Statement::Const(name, ..)
+            | Statement::Fn(name, ..)
+            | Statement::Import(_, Some(name), _) => {
+                binder.globals.insert(name.clone());
+            }
+            Statement::Import(_, None, _) => binder.open_imports = true,
+            _ => {}
+        }
+    }
+
+    for statement in statements {
+        binder.statement(statement);
+    }
+
+    // Whatever functions used that's still unanswered can only be a global
+    // declared after them.
+    for Pending {
+        name,
+        span,
+        read,
+        write,
+        ..
+    } in std::mem::take(&mut binder.pending)
+    {
+        match binder.scopes[0].get(&name) {
+            Some(&definition) => binder.refer(definition, span, read),
+            None if write => binder.undeclared(name, span),
+            None => {}
+        }
+    }
+
+    let mut analysis = binder.analysis;
+    let tree = cst::parse(source);
+    for definition in &mut analysis.definitions {
+        definition.visible = if definition.global {
+            Span {
+                start: 0,
+                end: source.len(),
+            }
+        } else {
+            Span {
+                start: definition.span.start,
+                end: visible_end(&tree, definition.span, source.len()),
+            }
+        };
+    }
+    analysis
+}
+
+// A local is visible to the end of the innermost block around it. Names
+// declared in a header, like parameters or a loop variable, are visible to
+// the end of the block after the header.
+fn visible_end(node: &SyntaxNode, span: Span, end: usize) -> usize {
+    for (i, child) in node.children.iter().enumerate() {
+        let SyntaxElement::Node(child) = child else {
+            continue;
+        };
+        let around = child.span();
+        if around.start > span.start || span.end > around.end {
+            continue;
+        }
+
+        let end = match child.kind {
+            NodeKind::Block => around.end,
+            NodeKind::Group => node.children[i + 1..]
+                .iter()
+                .find_map(|sibling| match sibling {
+                    SyntaxElement::Node(body) if body.kind == NodeKind::Block => {
+                        Some(body.span().end)
+                    }
+                    _ => None,
+                })
+                .unwrap_or(end),
+            _ => end,
+        };
+        return visible_end(child, span, end);
+    }
+
+    end
+}
+
+/// Finds where names and operators are in the source. Walks over the tree
+/// happen in source order, so each thing looked for is the next one of its
+/// kind after the last thing found, as long as every one of that kind is
+/// looked for.
+pub struct Locator {
+    lexemes: Vec<Lexeme>,
+    next: usize,
+}
+
+impl Locator {
+    pub fn new(source: &str) -> Locator {
+        fn flatten(lexemes: Vec<Lexeme>, flat: &mut Vec<Lexeme>) {
+            for lexeme in lexemes {
+                let parts = match &lexeme.token {
+                    Token::Interpolation(_, parts) => parts.clone(),
+                    _ => Vec::new(),
+                };
+                flat.push(lexeme);
+                for part in parts {
+                    if let StringPart::Code(code) = part {
+                        flatten(code, flat);
+                    }
+                }
+            }
+        }
+
+        let mut lexemes = Vec::new();
+        flatten(scan(source, 1).0, &mut lexemes);
+        Locator { lexemes, next: 0 }
+    }
+
+    pub fn find(&mut self, matches: impl Fn(&Token) -> bool) -> Span {
+        match self.lexemes[self.next..]
+            .iter()
+            .position(|lexeme| matches(&lexeme.token))
+        {
+            Some(i) => {
+                self.next += i + 1;
+                self.lexemes[self.next - 1].span
+            }
+            None => Span::default(),
+        }
+    }
+
+    pub fn name(&mut self, name: &str) -> Span {
+        self.find(|token| matches!(token, Token::Identifier(found) if found == name))
+    }
+
+    /// The parenthesized condition straight after the last token found.
+    pub fn condition(&self) -> Span {
+        let mut depth = 0;
+        for lexeme in &self.lexemes[self.next..] {
+            match lexeme.token {
+                Token::LeftParen => depth += 1,
+                Token::RightParen => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                return Span {
+                    start: self.lexemes[self.next].span.start,
+                    end: lexeme.span.end,
+                };
+            }
+        }
+        Span::default()
+    }
+}
+
+struct Binder {
+    locator: Locator,
+    /// Each scope's names, as indexes into the definitions.
+    scopes: Vec<HashMap<String, usize>>,
+    /// Every name declared at the top level, anywhere in the file.
+    globals: HashSet<String>,
+    /// An import without `as` can define any name.
+    open_imports: bool,
+    functions: usize,
+    pending: Vec<Pending>,
+    analysis: Analysis,
+}
+
+impl Binder {
+    fn declare(&mut self, name: &str, kind: Kind, span: Span, details: Details) {
+        let depth = self.scopes.len() - 1;
+        let shadows = self.scopes[..depth]
+            .iter()
+            .any(|scope| scope.contains_key(name));
+
+        let definition = self.analysis.definitions.len();
+        self.analysis.definitions.push(Definition {
+            name: name.to_string(),
+            kind,
+            span,
+            visible: Span::default(),
+            global: depth == 0,
+            params: details.params,
+            doc: details.doc,
+            used: false,
+            shadows: depth > 0 && shadows,
+        });
+        self.analysis.references.push((span, definition));
+        self.scopes[depth].insert(name.to_string(), definition);
+    }
+
+    fn scoped(&mut self, body: impl FnOnce(&mut Binder)) {
+        self.scopes.push(HashMap::new());
+        body(self);
+
+        let depth = self.scopes.len() - 1;
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+        let mut answered = Vec::new();
+        self.pending.retain_mut(|pending| {
+            if pending.opened <= depth {
+                return true;
+            }
+            match scope.get(&pending.name) {
+                Some(&definition) => {
+                    answered.push((definition, pending.span, pending.read));
+                    false
+                }
+                None => {
+                    pending.opened = depth;
+                    true
+                }
+            }
+        });
+        for (definition, span, read) in answered {
+            self.refer(definition, span, read);
+        }
+    }
+
+    fn refer(&mut self, definition: usize, span: Span, read: bool) {
+        self.analysis.references.push((span, definition));
+        if read {
+            self.analysis.definitions[definition].used = true;
+        }
+    }
+
+    fn undeclared(&mut self, name: String, span: Span) {
+        let declared = self.open_imports
+            || natives::lookup(&name).is_some()
+            || natives::constant(&name).is_some();
+        if !declared {
+            self.analysis.undeclared.push((name, span));
+        }
+    }
+
+    // A mention of `name` that reads it, writes it, or both.
+    fn mention(&mut self, name: &str, read: bool, write: bool) {
+        let span = self.locator.name(name);
+        self.resolve(name, span, read, write);
+    }
+
+    fn resolve(&mut self, name: &str, span: Span, read: bool, write: bool) {
+        let found = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied());
+
+        match found {
+            Some(definition) => self.refer(definition, span, read),
+            // Outside functions, code runs before any later local exists, so
+            // only a global can answer it.
+            None if self.functions > 0 || self.globals.contains(name) => {
+                let opened = if self.functions > 0 {
+                    self.scopes.len()
+                } else {
+                    0
+                };
+                self.pending.push(Pending {
+                    name: name.to_string(),
+                    opened,
+                    span,
+                    read,
+                    write,
+                });
+            }
+            None if write => self.undeclared(name.to_string(), span),
+            None => {}
+        }
+    }
+
+    fn function(&mut self, params: &[Token], body: &Statement) {
+        self.functions += 1;
+        self.scoped(|binder| {
+            for param in params {
+                if let Token::Identifier(name) = param {
+                    let span = binder.locator.name(name);
+                    binder.declare(name, Kind::Parameter, span, Details::default());
+                }
+            }
+            binder.statement(body);
+        });
+        self.functions -= 1;
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Block(statements) => self.scoped(|binder| {
+                for statement in statements {
+                    binder.statement(statement);
+                }
+            }),
+            Statement::Declaration(name, initializer, doc)
+            | Statement::Const(name, initializer, doc) => {
+                let kind = match statement {
+                    Statement::Const(..) => Kind::Constant,
+                    _ => Kind::Variable,
+                };
+                let span = self.locator.name(name);
+                self.expression(initializer);
+                let params = match initializer {
+                    Expression::Lambda(params, _) => Some(param_names(params)),
+                    _ => None,
+                };
+                self.declare(
+                    name,
+                    kind,
+                    span,
+                    Details {
+                        params,
+                        doc: doc.clone(),
+                    },
+                );
+            }
+            Statement::Expression(expression)
+            | Statement::Print(expression)
+            | Statement::Return(expression)
+            | Statement::Throw(expression, _) => self.expression(expression),
+            Statement::IfElse(condition, then, otherwise) => {
+                self.expression(condition);
+                self.statement(then);
+                if let Some(otherwise) = otherwise {
+                    self.statement(otherwise);
+                }
+            }
+            Statement::While(condition, body) => {
+                self.expression(condition);
+                self.statement(body);
+            }
+            Statement::For(initializer, condition, increment, body) => {
+                if let Some(initializer) = initializer {
+                    self.statement(initializer);
+                }
+                for expression in condition.iter().chain(increment) {
+                    self.expression(expression);
+                }
+                self.scoped(|binder| binder.statement(body));
+            }
+            Statement::ForIn(name, iterable, body, _) => {
+                let span = self.locator.name(name);
+                self.expression(iterable);
+                self.scoped(|binder| {
+                    binder.declare(name, Kind::Variable, span, Details::default());
+                    binder.statement(body);
+                });
+            }
+            Statement::Fn(name, params, body, doc) => {
+                let span = self.locator.name(name);
+                let details = Details {
+                    params: Some(param_names(params)),
+                    doc: doc.clone(),
+                };
+                self.declare(name, Kind::Function, span, details);
+                self.function(params, body);
+            }
+            Statement::Try(body, catch, finally) => {
+                self.statement(body);
+                if let Some((name, handler)) = catch {
+                    let span = self.locator.name(name);
+                    self.scoped(|binder| {
+                        binder.declare(name, Kind::Variable, span, Details::default());
+                        binder.statement(handler);
+                    });
+                }
+                if let Some(finally) = finally {
+                    self.statement(finally);
+                }
+            }
+            Statement::Import(_, alias, _) => {
+                if let Some(alias) = alias {
+                    let span = self.locator.name(alias);
+                    self.declare(alias, Kind::Variable, span, Details::default());
+                }
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Assignment(Primary::Identifier(name, _), value, _) => {
+                // The target comes first in the source, so it's found before
+                // the value, but it's only written once the value is known.
+                let span = self.locator.name(name);
+                self.expression(value);
+                self.resolve(name, span, false, true);
+            }
+            Expression::Assignment(_, value, _) => self.expression(value),
+            Expression::CompoundAssignment(target, _, value, _) => {
+                self.target(target);
+                self.expression(value);
+            }
+            Expression::Increment(target, _, _, _) => self.target(target),
+            Expression::Binary(left, _, right, _) => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::Unary(_, operand, _) => self.expression(operand),
+            Expression::Primary(Primary::Identifier(name, _)) => self.mention(name, true, false),
+            Expression::Primary(Primary::Function(name, args, _)) => {
+                self.mention(name, true, false);
+                self.expressions(args);
+            }
+            Expression::Primary(Primary::Grouping(inner)) => self.expression(inner),
+            Expression::Primary(_) => {}
+            Expression::Conditional(condition, then, otherwise) => {
+                self.expression(condition);
+                self.expression(then);
+                self.expression(otherwise);
+            }
+            Expression::Lambda(params, body) => self.function(params, body),
+            Expression::Call(callee, args, _) => {
+                self.expression(callee);
+                self.expressions(args);
+            }
+            Expression::Interpolation(parts) | Expression::List(parts) => self.expressions(parts),
+            Expression::Map(entries, _) => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            Expression::Index(object, index, _) => {
+                self.expression(object);
+                self.expression(index);
+            }
+            Expression::Get(object, name, _) => {
+                self.expression(object);
+                self.locator.name(name);
+            }
+            Expression::IndexAssignment(object, index, value, _) => {
+                self.expression(object);
+                self.expression(index);
+                self.expression(value);
+            }
+        }
+    }
+
+    fn expressions(&mut self, expressions: &[Expression]) {
+        for expression in expressions {
+            self.expression(expression);
+        }
+    }
+
+    // `x += 1` and `x++` read `x` as well as writing it.
+    fn target(&mut self, target: &Expression) {
+        match target {
+            Expression::Primary(Primary::Identifier(name, _)) => self.mention(name, true, true),
+            target => self.expression(target),
+        }
+    }
+}
+
+// A mention that nothing in scope answered yet.
+struct Pending {
+    name: String,
+    /// How many scopes were open at the time. Closing any of them can still
+    /// answer it.
+    opened: usize,
+    span: Span,
+    read: bool,
+    write: bool,
+}
+
+#[derive(Default)]
+struct Details {
+    params: Option<Vec<String>>,
+    doc: Option<String>,
+}
+
+fn param_names(params: &[Token]) -> Vec<String> {
+    params.iter().map(Token::get_identifier).collect()
+}
@@ -1,8 +1,10 @@
-use crate::enums::token::{KEYWORD_MAP, Lexeme, Token};
+use crate::enums::token::{KEYWORD_MAP, Lexeme, Span, StringPart, Token};
 use crate::utils::get_file_contents;
 
 struct CharStream<'a> {
     chars: std::iter::Peekable<std::str::Chars<'a>>,
+    // Byte offset of the next char in the whole file.
+    offset: usize,
 }
 
 impl CharStream<'_> {
@@ -11,45 +13,137 @@ impl CharStream<'_> {
     }
 
     fn next(&mut self) -> Option<char> {
-        self.chars.next()
+        let next = self.chars.next();
+        if let Some(c) = next {
+            self.offset += c.len_utf8();
+        }
+        next
     }
 }
 
 pub fn tokenize(filename: &str) -> (Vec<Lexeme>, Vec<Token>) {
-    let mut lexemes: Vec<Lexeme> = Vec::new();
-    let mut errors: Vec<Token> = Vec::new();
+    scan(&get_file_contents(filename), 1)
+}
+
+/// Tokenizes `source`, numbering lines from `first_line`. Each lexeme's span
+/// is its byte range in `source`.
+pub fn scan(source: &str, first_line: usize) -> (Vec<Lexeme>, Vec<Token>) {
+    let (lexemes, errors) = scan_from(source, first_line, 0);
+    (
+        lexemes,
+        errors.into_iter().map(|error| error.token).collect(),
+    )
+}
+
+/// Like `scan`, but each error comes as a lexeme spanning the text it was
+/// raised on.
+pub fn scan_spanned(source: &str, first_line: usize) -> (Vec<Lexeme>, Vec<Lexeme>) {
+    scan_from(source, first_line, 0)
+}
 
-    let file_contents = get_file_contents(filename);
+// Source embedded in a string is scanned from where it sits in the file, so
+// the spans of its lexemes are still offsets into the whole file.
+fn scan_from(source: &str, first_line: usize, first_offset: usize) -> (Vec<Lexeme>, Vec<Lexeme>) {
+    let mut lexemes: Vec<Lexeme> = Vec::new();
+    let mut errors: Vec<Lexeme> = Vec::new();
 
-    if !file_contents.is_empty() {
-        let chars = file_contents.chars().peekable();
-        let mut tokens = CharStream { chars };
+    if !source.is_empty() {
+        let chars = source.chars().peekable();
+        let mut tokens = CharStream {
+            chars,
+            offset: first_offset,
+        };
 
-        let mut line_number = 1;
+        let mut line_number = first_line;
+        let mut doc: Vec<String> = Vec::new();
 
-        while let Some(token) = tokens.next() {
+        loop {
+            let start = tokens.offset;
+            let Some(token) = tokens.next() else {
+                break;
+            };
+            let line = line_number;
             let token = match token {
                 '(' => Token::LeftParen,
                 ')' => Token::RightParen,
                 '{' => Token::LeftBrace,
                 '}' => Token::RightBrace,
+                '[' => Token::LeftBracket,
+                ']' => Token::RightBracket,
                 '.' => Token::Dot,
                 ',' => Token::Comma,
-                '+' => Token::Plus,
-                '-' => Token::Minus,
-                '*' => Token::Star,
+                '+' => match tokens.peek() {
+                    Some('+') => {
+                        tokens.next();
+                        Token::PlusPlus
+                    }
+                    _ => get_equal_token(&mut tokens, Token::PlusEqual, Token::Plus),
+                },
+                '-' => match tokens.peek() {
+                    Some('-') => {
+                        tokens.next();
+                        Token::MinusMinus
+                    }
+                    _ => get_equal_token(&mut tokens, Token::MinusEqual, Token::Minus),
+                },
+                '*' => match tokens.peek() {
+                    Some('*') => {
+                        tokens.next();
+                        Token::StarStar
+                    }
+                    _ => get_equal_token(&mut tokens, Token::StarEqual, Token::Star),
+                },
+                '%' => get_equal_token(&mut tokens, Token::PercentEqual, Token::Percent),
+                // `//` already starts a comment, so integer division is `~/`.
+                '~' => match tokens.peek() {
+                    Some('/') => {
+                        tokens.next();
+                        Token::TildeSlash
+                    }
+                    _ => Token::Error(token, line_number),
+                },
                 ';' => Token::SemiColon,
-                '=' => get_equal_token(&mut tokens, Token::EqualEqual, Token::Equal),
+                ':' => Token::Colon,
+                '?' => match tokens.peek() {
+                    Some('?') => {
+                        tokens.next();
+                        Token::QuestionQuestion
+                    }
+                    _ => Token::Question,
+                },
+                '=' => match tokens.peek() {
+                    Some('>') => {
+                        tokens.next();
+                        Token::Arrow
+                    }
+                    _ => get_equal_token(&mut tokens, Token::EqualEqual, Token::Equal),
+                },
                 '!' => get_equal_token(&mut tokens, Token::BangEqual, Token::Bang),
                 '>' => get_equal_token(&mut tokens, Token::GreaterEqual, Token::Greater),
                 '<' => get_equal_token(&mut tokens, Token::LessEqual, Token::Less),
                 '/' => match tokens.peek() {
                     Some('/') => {
                         tokens.next();
+                        // Exactly three slashes make a doc comment; four or
+                        // more are an ordinary comment, as in Rust.
+                        if tokens.peek() == Some('/') {
+                            tokens.next();
+                            if tokens.peek() != Some('/') {
+                                doc.push(get_doc_comment(&mut tokens));
+                                continue;
+                            }
+                        }
                         remove_comment(&mut tokens);
                         continue;
                     }
-                    _ => Token::Division,
+                    Some('*') => {
+                        tokens.next();
+                        match remove_block_comment(&mut tokens, &mut line_number) {
+                            Ok(()) => continue,
+                            Err(error) => error,
+                        }
+                    }
+                    _ => get_equal_token(&mut tokens, Token::DivisionEqual, Token::Division),
                 },
                 ' ' | '\t' => {
                     continue;
@@ -58,7 +152,7 @@ pub fn tokenize(filename: &str) -> (Vec<Lexeme>, Vec<Token>) {
                     line_number += 1;
                     continue;
                 }
-                '"' => get_string_token(&mut tokens, line_number),
+                '"' => get_string_token(&mut tokens, &mut line_number),
                 number if token.is_numeric() => get_numeric_token(&mut tokens, number, line_number),
                 identifier if token.is_alphabetic() || token == '_' => {
                     get_identifier(&mut tokens, identifier)
@@ -66,12 +160,26 @@ pub fn tokenize(filename: &str) -> (Vec<Lexeme>, Vec<Token>) {
                 _ => Token::Error(token, line_number),
             };
 
+            let span = Span {
+                start,
+                end: tokens.offset,
+            };
             match token {
-                Token::ErrorString(_, _) | Token::Error(_, _) => {
-                    errors.push(token);
+                Token::ErrorString(_, _) | Token::Error(_, _) | Token::LexError(_, _) => {
+                    errors.push(Lexeme {
+                        token,
+                        line_number: line,
+                        span,
+                        doc: None,
+                    });
                 }
                 _ => {
-                    let lexeme = Lexeme { token, line_number };
+                    let lexeme = Lexeme {
+                        token,
+                        line_number: line,
+                        span,
+                        doc: (!doc.is_empty()).then(|| std::mem::take(&mut doc).join("\n")),
+                    };
                     lexemes.push(lexeme);
                 }
             }
@@ -94,6 +202,54 @@ fn remove_comment(tokens: &mut CharStream) {
     }
 }
 
+/// Skips a `/* ... */` comment after its opening `/*`. Block comments nest,
+/// so `/* a /* b */ c */` is one comment. An unterminated comment is
+/// reported at the line it started on.
+fn remove_block_comment(tokens: &mut CharStream, line: &mut usize) -> Result<(), Token> {
+    let start_line = *line;
+    let mut depth = 1;
+
+    while let Some(next) = tokens.next() {
+        match next {
+            '\n' => *line += 1,
+            '*' if tokens.peek() == Some('/') => {
+                tokens.next();
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(());
+                }
+            }
+            '/' if tokens.peek() == Some('*') => {
+                tokens.next();
+                depth += 1;
+            }
+            _ => {}
+        }
+    }
+
+    Err(Token::LexError(
+        "Unterminated block comment.".to_string(),
+        start_line,
+    ))
+}
+
+// The rest of a `///` line, less the space that usually follows the slashes.
+fn get_doc_comment(tokens: &mut CharStream) -> String {
+    let mut text = String::new();
+    while let Some(next) = tokens.peek() {
+        if next == '\n' {
+            break;
+        }
+        tokens.next();
+        text.push(next);
+    }
+
+    match text.strip_prefix(' ') {
+        Some(text) => text.to_string(),
+        None => text,
+    }
+}
+
 fn get_identifier(tokens: &mut CharStream, token: char) -> Token {
     let mut word = token.to_string();
 
@@ -150,22 +306,169 @@ fn parse_number(string: String, line: usize) -> Token {
     }
 }
 
-fn get_string_token(tokens: &mut CharStream, line: usize) -> Token {
+/// Scans a string literal after its opening quote. Newlines inside the
+/// string advance `line`, and an unterminated string is reported at the line
+/// it started on. After a bad escape the rest of the string is still consumed,
+/// so scanning picks up again after the closing quote.
+///
+/// A string containing `${...}` becomes an `Interpolation` whose embedded
+/// expressions are tokenized separately.
+fn get_string_token(tokens: &mut CharStream, line: &mut usize) -> Token {
+    let start_line = *line;
+    let mut raw = String::new();
     let mut string = String::new();
-    while let Some(next) = tokens.peek() {
+    let mut parts: Vec<StringPart> = Vec::new();
+    let mut error: Option<Token> = None;
+
+    while let Some(next) = tokens.next() {
         match next {
             '"' => {
+                if let Some(error) = error {
+                    return error;
+                }
+                if parts.is_empty() {
+                    return Token::String(raw, string);
+                }
+                if !string.is_empty() {
+                    parts.push(StringPart::Text(string));
+                }
+                return Token::Interpolation(raw, parts);
+            }
+            '$' if tokens.peek() == Some('{') => {
                 tokens.next();
-                return Token::String(string);
+                let offset = tokens.offset;
+                let Some(source) = get_embedded_source(tokens) else {
+                    break;
+                };
+
+                if !string.is_empty() {
+                    parts.push(StringPart::Text(std::mem::take(&mut string)));
+                }
+
+                let (lexemes, errors) = scan_from(&source, *line, offset);
+                if let Some(first) = errors.into_iter().next() {
+                    error.get_or_insert(first.token);
+                }
+                parts.push(StringPart::Code(lexemes));
+
+                *line += source.matches('\n').count();
+                raw.push_str("${");
+                raw.push_str(&source);
+                raw.push('}');
+            }
+            '\\' => {
+                raw.push(next);
+                match get_escape(tokens, &mut raw) {
+                    Ok(c) => string.push(c),
+                    Err(message) => {
+                        error.get_or_insert(Token::LexError(message, *line));
+                    }
+                }
             }
             c => {
-                tokens.next();
+                if c == '\n' {
+                    *line += 1;
+                }
+                raw.push(c);
                 string.push(c);
             }
         }
     }
 
-    Token::ErrorString(string, line)
+    Token::ErrorString(string, start_line)
+}
+
+fn get_escape(tokens: &mut CharStream, raw: &mut String) -> Result<char, String> {
+    let escape = match tokens.peek() {
+        Some(escape) if escape != '\n' => escape,
+        _ => return Err("Unfinished escape sequence.".to_string()),
+    };
+    tokens.next();
+    raw.push(escape);
+
+    match escape {
+        'n' => Ok('\n'),
+        't' => Ok('\t'),
+        'r' => Ok('\r'),
+        '0' => Ok('\0'),
+        '$' => Ok('$'),
+        '\\' => Ok('\\'),
+        '"' => Ok('"'),
+        'u' => get_unicode_escape(tokens, raw),
+        _ => Err(format!("Unknown escape sequence '\\{}'.", escape)),
+    }
+}
+
+// `\u{...}` with one to six hex digits naming a Unicode scalar value.
+fn get_unicode_escape(tokens: &mut CharStream, raw: &mut String) -> Result<char, String> {
+    if tokens.peek() != Some('{') {
+        return Err("Expected '{' after '\\u'.".to_string());
+    }
+    tokens.next();
+    raw.push('{');
+
+    let mut digits = String::new();
+    while let Some(next) = tokens.peek() {
+        if next == '"' || next == '\n' {
+            break;
+        }
+        tokens.next();
+        raw.push(next);
+        if next == '}' {
+            return u32::from_str_radix(&digits, 16)
+                .ok()
+                .filter(|_| (1..=6).contains(&digits.len()))
+                .and_then(char::from_u32)
+                .ok_or_else(|| format!("Invalid Unicode escape '\\u{{{}}}'.", digits));
+        }
+        digits.push(next);
+    }
+
+    Err("Unterminated Unicode escape.".to_string())
+}
+
+/// Copies the source of an embedded `${...}` expression up to its closing
+/// brace, skipping over any string literals inside it. Returns `None` if the
+/// input ends first.
+fn get_embedded_source(tokens: &mut CharStream) -> Option<String> {
+    let mut source = String::new();
+    let mut depth = 0;
+
+    while let Some(next) = tokens.next() {
+        match next {
+            '}' if depth == 0 => return Some(source),
+            '}' => depth -= 1,
+            '{' => depth += 1,
+            '"' => {
+                source.push(next);
+                copy_string_source(tokens, &mut source)?;
+                continue;
+            }
+            _ => {}
+        }
+        source.push(next);
+    }
+
+    None
+}
+
+fn copy_string_source(tokens: &mut CharStream, source: &mut String) -> Option<()> {
+    while let Some(next) = tokens.next() {
+        source.push(next);
+        match next {
+            '"' => return Some(()),
+            '\\' => source.push(tokens.next()?),
+            '$' if tokens.peek() == Some('{') => {
+                tokens.next();
+                source.push('{');
+                source.push_str(&get_embedded_source(tokens)?);
+                source.push('}');
+            }
+            _ => {}
+        }
+    }
+
+    None
 }
 
 fn get_equal_token(tokens: &mut CharStream, if_equal_token: Token, token: Token) -> Token {
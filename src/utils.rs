@@ -22,3 +22,79 @@ where
         println!("{}", x);
     }
 }
+
+/// The argument following `name` in `flags`, as in `--runs 5`.
+pub fn flag_value<'a>(flags: &'a [String], name: &str) -> Option<&'a str> {
+    flags
+        .iter()
+        .position(|flag| flag == name)
+        .and_then(|i| flags.get(i + 1))
+        .map(String::as_str)
+}
+
+// Flags that take the argument after them as their value.
+const VALUE_FLAGS: &[&str] = &[
+    "--disable-natives",
+    "--config",
+    "--format",
+    "--runs",
+    "--compare",
+];
+
+/// Splits the arguments after the command into its one file and its flags,
+/// which can come before or after the file. A flag keeps its value next to
+/// it, so `flag_value` still finds it. Any other argument is an error.
+pub fn split_arguments(args: &[String]) -> Result<(String, Vec<String>), String> {
+    let mut filename = None;
+    let mut flags = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg.starts_with("--") {
+            flags.push(arg.clone());
+            if VALUE_FLAGS.contains(&arg.as_str()) {
+                flags.extend(args.next().cloned());
+            }
+        } else if filename.is_none() {
+            filename = Some(arg.clone());
+        } else {
+            return Err(format!("Unexpected argument: {}", arg));
+        }
+    }
+
+    match filename {
+        Some(filename) => Ok((filename, flags)),
+        None => Err("Missing file argument.".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(args: &[&str]) -> Result<(String, Vec<String>), String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        split_arguments(&args)
+    }
+
+    #[test]
+    fn flags_can_come_before_or_after_the_file() {
+        let expected = ("a.lox".to_string(), vec!["--check".to_string()]);
+        assert_eq!(split(&["--check", "a.lox"]), Ok(expected.clone()));
+        assert_eq!(split(&["a.lox", "--check"]), Ok(expected));
+    }
+
+    #[test]
+    fn flag_values_stay_with_their_flag() {
+        let (filename, flags) = split(&["--format", "json", "a.lox", "--runs", "3"]).unwrap();
+        assert_eq!(filename, "a.lox");
+        assert_eq!(flag_value(&flags, "--format"), Some("json"));
+        assert_eq!(flag_value(&flags, "--runs"), Some("3"));
+    }
+
+    #[test]
+    fn the_file_is_required_and_unique() {
+        assert!(split(&["--check"]).is_err());
+        assert!(split(&["a.lox", "b.lox"]).is_err());
+    }
+}